@@ -0,0 +1,384 @@
+//! Small argument parser shared by every command: `--flag`, `--flag=value`, `--flag value`,
+//! combined short flags (`-rf`, `-vv`, `-tlib`) and `--` ending the options.
+use std::fmt;
+use std::str::FromStr;
+
+/// What the shell completions offer for a value
+#[derive(Clone, Copy)]
+pub enum Completion {
+    Nothing,
+    Dirs,
+    Words(&'static [&'static str]),
+    /// Values listed when completing by `kinoko completions --values <list>`
+    Values(&'static str),
+}
+
+/// An option a command accepts
+#[derive(Clone)]
+pub struct Opt {
+    pub long: &'static str,
+    pub short: Option<char>,
+    /// Name of the value the option takes, `None` for flags
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+    pub complete: Completion,
+}
+impl Opt {
+    pub fn flag(long: &'static str, short: Option<char>, help: &'static str) -> Opt {
+        return Opt { long: long, short: short, value: None, help: help, complete: Completion::Nothing };
+    }
+
+    pub fn value(long: &'static str, short: Option<char>, value: &'static str, help: &'static str) -> Opt {
+        return Opt { long: long, short: short, value: Some(value), help: help, complete: Completion::Nothing };
+    }
+
+    pub fn complete(mut self, complete: Completion) -> Opt {
+        self.complete = complete;
+        return self;
+    }
+
+    /// `-t, --template <name>`
+    pub fn signature(&self) -> String {
+        let mut signature = match self.short {
+            Some(short) => format!("-{}, --{}", short, self.long),
+            None => format!("--{}", self.long),
+        };
+        if let Some(value) = self.value {
+            signature.push_str(&format!(" <{}>", value));
+        }
+        return signature;
+    }
+}
+
+/// An argument that isn't an option, matched by position
+#[derive(Clone)]
+pub struct Positional {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub required: bool,
+    pub complete: Completion,
+}
+
+/// Everything a command accepts
+#[derive(Clone)]
+pub struct ArgSpec {
+    pub options: Vec<Opt>,
+    pub positionals: Vec<Positional>,
+    /// Name and help of what goes after `--`, without it `--` only stops option parsing
+    pub trailing: Option<(&'static str, &'static str)>,
+    /// Leaves the first positional and everything after it unparsed, for arguments in front of a command
+    pub stop_at_positional: bool,
+}
+impl ArgSpec {
+    pub fn new() -> ArgSpec {
+        return ArgSpec { options: Vec::new(), positionals: Vec::new(), trailing: None, stop_at_positional: false };
+    }
+
+    pub fn opt(mut self, opt: Opt) -> ArgSpec {
+        self.options.push(opt);
+        return self;
+    }
+
+    pub fn positional(mut self, name: &'static str, required: bool, help: &'static str) -> ArgSpec {
+        self.positionals.push(Positional { name: name, help: help, required: required, complete: Completion::Nothing });
+        return self;
+    }
+
+    /// Sets what the shell completions offer for the last positional added
+    pub fn completes(mut self, complete: Completion) -> ArgSpec {
+        if let Some(positional) = self.positionals.last_mut() {
+            positional.complete = complete;
+        }
+        return self;
+    }
+
+    pub fn trailing(mut self, name: &'static str, help: &'static str) -> ArgSpec {
+        self.trailing = Some((name, help));
+        return self;
+    }
+
+    fn find_long(&self, long: &str) -> Option<&Opt> {
+        return self.options.iter().find(|opt| opt.long == long);
+    }
+
+    fn find_short(&self, short: char) -> Option<&Opt> {
+        return self.options.iter().find(|opt| opt.short == Some(short));
+    }
+
+    /// `[-r] [--profile <name>] [dir] [-- <run-args>...]`
+    pub fn usage(&self) -> String {
+        let mut parts = Vec::new();
+        for opt in self.options.iter() {
+            let name = match opt.short {
+                Some(short) => format!("-{}", short),
+                None => format!("--{}", opt.long),
+            };
+            parts.push(match opt.value {
+                Some(value) => format!("[{} <{}>]", name, value),
+                None => format!("[{}]", name),
+            });
+        }
+        for positional in self.positionals.iter() {
+            parts.push(if positional.required { format!("<{}>", positional.name) } else { format!("[{}]", positional.name) });
+        }
+        if let Some((trailing, _)) = self.trailing {
+            parts.push(format!("[-- <{}>...]", trailing));
+        }
+        return parts.join(" ");
+    }
+
+    /// `Arguments:` and `Options:` sections of a command's help
+    pub fn help(&self) -> String {
+        let mut rows: Vec<(String, &str)> = Vec::new();
+        let mut text = String::new();
+        for positional in self.positionals.iter() {
+            rows.push((positional.name.to_string(), positional.help));
+        }
+        if let Some((trailing, help)) = self.trailing {
+            rows.push((format!("-- <{}>...", trailing), help));
+        }
+        let width = self.options.iter().map(|opt| opt.signature().len())
+            .chain(rows.iter().map(|(name, _)| name.len()))
+            .max().unwrap_or(0);
+        if !rows.is_empty() {
+            text.push_str("Arguments:\n");
+            for (name, help) in rows.iter() {
+                text.push_str(&format!("  {:<width$}  {}\n", name, help, width = width));
+            }
+        }
+        if !self.options.is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str("Options:\n");
+            for opt in self.options.iter() {
+                text.push_str(&format!("  {:<width$}  {}\n", opt.signature(), opt.help, width = width));
+            }
+        }
+        return text.trim_end().to_string();
+    }
+}
+
+#[derive(Debug)]
+pub enum ArgError {
+    /// `-h` or `--help` was passed
+    HelpRequested,
+    UnknownOption(String),
+    MissingValue(String),
+    UnexpectedValue(String),
+    InvalidValue(String, String),
+    MissingArgument(&'static str),
+    UnexpectedArgument(String),
+}
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    ArgError::HelpRequested => write!(f, "help was requested"),
+	    ArgError::UnknownOption(opt) => write!(f, "Unknown option: {}", opt),
+	    ArgError::MissingValue(opt) => write!(f, "Missing value after {}", opt),
+	    ArgError::UnexpectedValue(opt) => write!(f, "{} doesn't take a value", opt),
+	    ArgError::InvalidValue(opt, value) => write!(f, "Invalid value for {}: {}", opt, value),
+	    ArgError::MissingArgument(name) => write!(f, "Missing argument <{}>", name),
+	    ArgError::UnexpectedArgument(arg) => write!(f, "Unexpected argument: {}", arg),
+	}
+    }
+}
+
+/// What was found in the arguments, options are looked up by their long name
+pub struct ParsedArgs {
+    flags: Vec<&'static str>,
+    values: Vec<(&'static str, String)>,
+    positionals: Vec<(&'static str, String)>,
+    /// The first positional and everything after it, only with `stop_at_positional`
+    pub rest: Vec<String>,
+    /// Arguments after `--`
+    pub trailing: Vec<String>,
+}
+impl ParsedArgs {
+    pub fn flag(&self, long: &str) -> bool {
+        return self.count(long) > 0;
+    }
+
+    /// Times a flag was passed, `-vv` counts twice
+    pub fn count(&self, long: &str) -> usize {
+        return self.flags.iter().filter(|flag| **flag == long).count();
+    }
+
+    /// Last value given to an option
+    pub fn value(&self, long: &str) -> Option<&str> {
+        return self.values.iter().rev().find(|(name, _)| *name == long).map(|(_, value)| value.as_str());
+    }
+
+    /// Every value given to a repeatable option, in order
+    pub fn values(&self, long: &str) -> Vec<String> {
+        return self.values.iter().filter(|(name, _)| *name == long).map(|(_, value)| value.clone()).collect();
+    }
+
+    pub fn positional(&self, name: &str) -> Option<&str> {
+        return self.positionals.iter().find(|(known, _)| *known == name).map(|(_, value)| value.as_str());
+    }
+
+    /// Positional converted to `T`
+    pub fn positional_as<T: FromStr>(&self, name: &str) -> Result<Option<T>, ArgError> {
+        return match self.positional(name) {
+            None => Ok(None),
+            Some(value) => match value.parse::<T>() {
+                Ok(parsed) => Ok(Some(parsed)),
+                Err(_) => Err(ArgError::InvalidValue(format!("<{}>", name), value.to_string())),
+            },
+        };
+    }
+}
+
+pub fn parse(spec: &ArgSpec, args: Vec<String>) -> Result<ParsedArgs, ArgError> {
+    let mut parsed = ParsedArgs { flags: Vec::new(), values: Vec::new(), positionals: Vec::new(), rest: Vec::new(), trailing: Vec::new() };
+    let mut loose = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            match spec.trailing {
+                Some(_) => parsed.trailing.extend(args.by_ref()),
+                None => loose.extend(args.by_ref()),
+            }
+            break;
+        }
+        if arg == "-h" || arg == "--help" {
+            return Err(ArgError::HelpRequested);
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let opt = match spec.find_long(name) {
+                Some(opt) => opt,
+                None => return Err(ArgError::UnknownOption(format!("--{}", name))),
+            };
+            match (opt.value, inline_value) {
+                (None, None) => parsed.flags.push(opt.long),
+                (None, Some(_)) => return Err(ArgError::UnexpectedValue(format!("--{}", name))),
+                (Some(_), Some(value)) => parsed.values.push((opt.long, value)),
+                (Some(_), None) => match args.next() {
+                    Some(value) => parsed.values.push((opt.long, value)),
+                    None => return Err(ArgError::MissingValue(format!("--{}", name))),
+                },
+            }
+            continue;
+        }
+        if arg.starts_with('-') && arg.len() > 1 {
+            // Combined short flags, a short option taking a value takes the rest of the argument or the next one
+            let shorts: Vec<char> = arg.chars().skip(1).collect();
+            for (idx, short) in shorts.iter().enumerate() {
+                let opt = match spec.find_short(*short) {
+                    Some(opt) => opt,
+                    None => return Err(ArgError::UnknownOption(format!("-{}", short))),
+                };
+                if opt.value.is_none() {
+                    parsed.flags.push(opt.long);
+                    continue;
+                }
+                let attached: String = shorts[idx + 1..].iter().collect();
+                if !attached.is_empty() {
+                    parsed.values.push((opt.long, attached));
+                } else {
+                    match args.next() {
+                        Some(value) => parsed.values.push((opt.long, value)),
+                        None => return Err(ArgError::MissingValue(format!("-{}", short))),
+                    }
+                }
+                break;
+            }
+            continue;
+        }
+        if spec.stop_at_positional {
+            parsed.rest.push(arg);
+            parsed.rest.extend(args.by_ref());
+            break;
+        }
+        loose.push(arg);
+    }
+
+    let mut loose = loose.into_iter();
+    for positional in spec.positionals.iter() {
+        match loose.next() {
+            Some(value) => parsed.positionals.push((positional.name, value)),
+            None if positional.required => return Err(ArgError::MissingArgument(positional.name)),
+            None => break,
+        }
+    }
+    if let Some(extra) = loose.next() {
+        return Err(ArgError::UnexpectedArgument(extra));
+    }
+    return Ok(parsed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ArgSpec {
+        return ArgSpec::new()
+            .opt(Opt::flag("run", Some('r'), "Run it"))
+            .opt(Opt::flag("verbose", Some('v'), "Say more"))
+            .opt(Opt::value("profile", Some('p'), "name", "Profile"))
+            .positional("dir", false, "Folder")
+            .trailing("run-args", "Passed to the head");
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        return args.iter().map(|arg| arg.to_string()).collect();
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        let parsed = parse(&spec(), args(&["-rvv"])).unwrap();
+        assert!(parsed.flag("run"));
+        assert_eq!(parsed.count("verbose"), 2);
+    }
+
+    #[test]
+    fn short_option_takes_the_rest_or_the_next_argument() {
+        assert_eq!(parse(&spec(), args(&["-rprelease"])).unwrap().value("profile"), Some("release"));
+        assert_eq!(parse(&spec(), args(&["-p", "release"])).unwrap().value("profile"), Some("release"));
+    }
+
+    #[test]
+    fn long_option_values() {
+        assert_eq!(parse(&spec(), args(&["--profile=x"])).unwrap().value("profile"), Some("x"));
+        assert_eq!(parse(&spec(), args(&["--profile", "x", "--profile", "y"])).unwrap().values("profile"), vec!["x", "y"]);
+        assert!(matches!(parse(&spec(), args(&["--profile"])), Err(ArgError::MissingValue(_))));
+        assert!(matches!(parse(&spec(), args(&["--run=yes"])), Err(ArgError::UnexpectedValue(_))));
+    }
+
+    #[test]
+    fn double_dash_passes_everything_through() {
+        let parsed = parse(&spec(), args(&["-r", "dir", "--", "-r", "--profile", "x"])).unwrap();
+        assert_eq!(parsed.positional("dir"), Some("dir"));
+        assert_eq!(parsed.trailing, args(&["-r", "--profile", "x"]));
+        assert_eq!(parsed.count("run"), 1);
+    }
+
+    #[test]
+    fn double_dash_without_trailing_only_stops_options() {
+        let spec = ArgSpec::new().opt(Opt::flag("run", Some('r'), "Run it")).positional("dir", false, "Folder");
+        let parsed = parse(&spec, args(&["--", "-r"])).unwrap();
+        assert_eq!(parsed.positional("dir"), Some("-r"));
+        assert!(!parsed.flag("run"));
+    }
+
+    #[test]
+    fn unknown_options_and_arguments() {
+        assert!(matches!(parse(&spec(), args(&["--nope"])), Err(ArgError::UnknownOption(ref opt)) if opt == "--nope"));
+        assert!(matches!(parse(&spec(), args(&["-rx"])), Err(ArgError::UnknownOption(ref opt)) if opt == "-x"));
+        assert!(matches!(parse(&spec(), args(&["a", "b"])), Err(ArgError::UnexpectedArgument(ref arg)) if arg == "b"));
+        assert!(matches!(parse(&spec(), args(&["-h"])), Err(ArgError::HelpRequested)));
+    }
+
+    #[test]
+    fn stop_at_positional_leaves_the_rest() {
+        let mut spec = ArgSpec::new().opt(Opt::flag("verbose", Some('v'), "Say more"));
+        spec.stop_at_positional = true;
+        let parsed = parse(&spec, args(&["-v", "build", "-r"])).unwrap();
+        assert_eq!(parsed.rest, args(&["build", "-r"]));
+    }
+}
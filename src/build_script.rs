@@ -0,0 +1,232 @@
+//! Build scripts: a `build.rs` next to the kinoko.🍄 (or the `build-script:` key) is compiled and ran before the
+//! germination. Its `kinoko:` lines on stdout add cfgs and compile time environment variables to rustc.
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use data_structs::{Kinoko, Mushroom, GerminationError};
+use utility::{info, warn, verbose, debug, command_line};
+
+/// Looked for when the mushroom has no `build-script:`
+pub const DEFAULT_BUILD_SCRIPT: &'static str = "build.rs";
+
+/// What a build script asked for
+#[derive(Debug, Clone, Default)]
+pub struct BuildScriptOutput {
+    /// Passed as `--cfg`
+    pub cfgs: Vec<String>,
+    /// Set while rustc runs, for `env!`
+    pub envs: Vec<(String, String)>,
+    /// Paths the script only reruns for, it reruns on every germination without any
+    pub rerun_if_changed: Vec<String>,
+}
+impl BuildScriptOutput {
+    /// Reads the `kinoko:` directives, anything else the script printed is left alone
+    pub fn parse(kinoko: &Kinoko, stdout: &str) -> BuildScriptOutput {
+        let mut output = BuildScriptOutput::default();
+        for line in stdout.lines() {
+            let directive = match line.trim().strip_prefix("kinoko:") {
+                Some(directive) => directive,
+                None => continue,
+            };
+            let (key, value) = match directive.split_once('=') {
+                Some(key_value) => key_value,
+                None => {
+                    warn!(&kinoko.ctx, "Build script directive without a value: {}", line);
+                    continue;
+                },
+            };
+            match key {
+                "rustc-cfg" => output.cfgs.push(value.to_string()),
+                "rustc-env" => match value.split_once('=') {
+                    Some((name, value)) => output.envs.push((name.to_string(), value.to_string())),
+                    None => warn!(&kinoko.ctx, "Build script rustc-env needs NAME=VALUE, got `{}`", value),
+                },
+                "rerun-if-changed" => output.rerun_if_changed.push(value.to_string()),
+                _ => warn!(&kinoko.ctx, "Unknown build script directive: kinoko:{}", key),
+            }
+        }
+        return output;
+    }
+
+    /// Adds the cfgs, the environment variables and `OUT_DIR` to a rustc command
+    pub fn apply(&self, kinoko: &Kinoko, mushroom: &Mushroom, cmd: &mut Command) {
+        cmd.env("OUT_DIR", out_dir(kinoko, mushroom));
+        for cfg in self.cfgs.iter() {
+            cmd.arg("--cfg").arg(cfg);
+        }
+        for (name, value) in self.envs.iter() {
+            cmd.env(name, value);
+        }
+    }
+}
+
+/// The `build-script:` of the mushroom, or a `build.rs` next to it
+pub fn find_build_script(kinoko: &Kinoko, mushroom: &Mushroom) -> Option<PathBuf> {
+    if let Some(script) = &mushroom.build_script {
+        return Some(kinoko.cwd.join(script));
+    }
+    let script = kinoko.cwd.join(DEFAULT_BUILD_SCRIPT);
+    return if script.is_file() { Some(script) } else { None };
+}
+
+/// Folder the build script is compiled into and keeps its last output, next to the head
+pub fn build_script_dir(kinoko: &Kinoko, mushroom: &Mushroom) -> PathBuf {
+    let head_path = kinoko.cwd.join(&mushroom.head);
+    let head_dir = match head_path.parent() {
+        Some(dir) => dir.to_path_buf(),
+        None => kinoko.cwd.clone(),
+    };
+    return head_dir.join(".build-script");
+}
+
+/// `OUT_DIR` of the build script, where it writes generated code
+pub fn out_dir(kinoko: &Kinoko, mushroom: &Mushroom) -> PathBuf {
+    return build_script_dir(kinoko, mushroom).join("out");
+}
+
+/// Stdout of the last run, kept to reuse its directives
+pub fn output_path(kinoko: &Kinoko, mushroom: &Mushroom) -> PathBuf {
+    return build_script_dir(kinoko, mushroom).join("output");
+}
+
+/// rustc and profile of the last run, either changing compiles and runs the script again
+fn stamp_path(kinoko: &Kinoko, mushroom: &Mushroom) -> PathBuf {
+    return build_script_dir(kinoko, mushroom).join("stamp");
+}
+
+fn stamp(kinoko: &Kinoko, rustc: &OsStr) -> String {
+    return format!("rustc={}\nprofile={}\n", rustc.to_string_lossy(), kinoko.profile.clone().unwrap_or_default());
+}
+
+fn executable_path(kinoko: &Kinoko, mushroom: &Mushroom) -> PathBuf {
+    let mut name = OsString::from("build-script");
+    name.push(std::env::consts::EXE_SUFFIX);
+    return build_script_dir(kinoko, mushroom).join(name);
+}
+
+/// Output of the last run, without running anything
+pub fn cached_output(kinoko: &Kinoko, mushroom: &Mushroom) -> Option<BuildScriptOutput> {
+    find_build_script(kinoko, mushroom)?;
+    let stdout = fs::read_to_string(output_path(kinoko, mushroom)).ok()?;
+    return Some(BuildScriptOutput::parse(kinoko, &stdout));
+}
+
+/// Compiles and runs the build script when it, what it watches, the rustc or the profile changed, `None` without a build script
+pub fn run_build_script(kinoko: &Kinoko, mushroom: &Mushroom, rustc: &OsStr) -> Result<Option<BuildScriptOutput>, GerminationError> {
+    let script = match find_build_script(kinoko, mushroom) {
+        Some(script) => script,
+        None => return Ok(None),
+    };
+    if !script.is_file() {
+        return Err(GerminationError::MissingBuildScript(script));
+    }
+    verbose!(&kinoko.ctx, "Mushroom.build-script = {}", script.display());
+    let out_dir = out_dir(kinoko, mushroom);
+    if let Err(err) = fs::create_dir_all(&out_dir) {
+        return Err(GerminationError::NoHeadDir(err));
+    }
+
+    let stamp = stamp(kinoko, rustc);
+    let stamp_changed = fs::read_to_string(stamp_path(kinoko, mushroom)).map(|last| last != stamp).unwrap_or(true);
+    let executable = executable_path(kinoko, mushroom);
+    if stamp_changed || is_newer(&script, &executable) {
+        let mut cmd = Command::new(rustc);
+        cmd.arg("-o").arg(&executable).arg(&script);
+        if let Some(edition) = &mushroom.edition {
+            cmd.arg("--edition").arg(edition);
+        }
+        debug!(&kinoko.ctx, "{}", command_line(&cmd));
+        match cmd.status() {
+            Err(err) => return Err(GerminationError::RustcUnavailable(err)),
+            Ok(status) if !status.success() => return Err(GerminationError::BuildScriptFailed(String::from("it failed to compile"))),
+            Ok(_) => info!(&kinoko.ctx, "Compiled build script: {}", script.display()),
+        }
+    }
+
+    let output_path = output_path(kinoko, mushroom);
+    if let Some(cached) = cached_output(kinoko, mushroom) {
+        let changed = stamp_changed
+            || cached.rerun_if_changed.is_empty()
+            || is_newer(&executable, &output_path)
+            || cached.rerun_if_changed.iter().any(|path| is_newer(&kinoko.cwd.join(path), &output_path));
+        if !changed {
+            verbose!(&kinoko.ctx, "Build script is up to date, reusing its output");
+            return Ok(Some(cached));
+        }
+    }
+
+    let mut cmd = Command::new(&executable);
+    cmd.current_dir(&kinoko.cwd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .env("OUT_DIR", &out_dir)
+        .env("KINOKO_PROJECT_DIR", &kinoko.cwd)
+        .env("KINOKO_PROFILE", kinoko.profile.clone().unwrap_or_default())
+        .env("RUSTC", rustc);
+    debug!(&kinoko.ctx, "{}", command_line(&cmd));
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(err) => return Err(GerminationError::BuildScriptFailed(format!("it failed to start: {}", err))),
+    };
+    if !output.status.success() {
+        let failure = match output.status.code() {
+            Some(code) => format!("it exited with {}", code),
+            None => String::from("it was terminated by a signal"),
+        };
+        return Err(GerminationError::BuildScriptFailed(failure));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    for line in stdout.lines().filter(|line| !line.trim().starts_with("kinoko:")) {
+        verbose!(&kinoko.ctx, "build script: {}", line);
+    }
+    if let Err(err) = fs::write(&output_path, &stdout).and_then(|_| fs::write(stamp_path(kinoko, mushroom), &stamp)) {
+        warn!(&kinoko.ctx, "Failed to keep the build script output in {}: {}", output_path.display(), err);
+    }
+    return Ok(Some(BuildScriptOutput::parse(kinoko, &stdout)));
+}
+
+/// Whether `path` changed after `than` was written, a missing `than` is always older
+fn is_newer(path: &Path, than: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let than: SystemTime = match modified(than) {
+        Some(than) => than,
+        None => return true,
+    };
+    return match modified(path) {
+        Some(modified) => modified > than,
+        None => true,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(stdout: &str) -> BuildScriptOutput {
+        return BuildScriptOutput::parse(&Kinoko::at("/nowhere").build(), stdout);
+    }
+
+    #[test]
+    fn reads_kinoko_directives() {
+        let output = parse("kinoko:rustc-cfg=has_table\nkinoko:rustc-env=NOTE=a=b\n  kinoko:rerun-if-changed=data/table.csv\n");
+        assert_eq!(output.cfgs, vec!["has_table"]);
+        assert_eq!(output.envs, vec![(String::from("NOTE"), String::from("a=b"))]);
+        assert_eq!(output.rerun_if_changed, vec!["data/table.csv"]);
+    }
+
+    #[test]
+    fn cfgs_keep_their_values() {
+        assert_eq!(parse("kinoko:rustc-cfg=feature=\"fancy\"\n").cfgs, vec!["feature=\"fancy\""]);
+    }
+
+    #[test]
+    fn ignores_everything_else() {
+        let output = parse("plain line\ncargo:rustc-cfg=unix\nkinoko:rustc-env=NO_VALUE\nkinoko:unknown=1\nkinoko:no-equals\n");
+        assert!(output.cfgs.is_empty());
+        assert!(output.envs.is_empty());
+        assert!(output.rerun_if_changed.is_empty());
+    }
+}
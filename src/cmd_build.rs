@@ -1,80 +1,100 @@
 use std::process::Command;
-use std::path::PathBuf;
-use data_structs::*;
-use utility::{info, error};
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use kinoko::rust_project;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use kinoko::utility::{info, warn, error, verbose, command_line};
 
-type CmdResult = Result<(), String>;
+type CmdResult = Result<(), KinokoError>;
 const COMMAND_NAME: &'static str = "build";
 
-pub fn check_args(argv: &Vec<String>) -> bool {
-    if argv.len() < 1 {
-        return false;
+pub struct BuildCommand;
+
+impl Subcommand for BuildCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
     }
 
-    let cmd_name = COMMAND_NAME.to_string();
-    if argv[0] == cmd_name {
-        return true;
+    fn aliases(&self) -> &'static [&'static str] {
+	&["build-run"]
     }
-    if argv[0] == format!("{}-run", COMMAND_NAME) {
-        return true;
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::flag("run", Some('r'), "Run the head after it germinates"))
+	    .opt(Opt::value("profile", None, "name", "Add the rustc flags of the mushroom's `profile.<name>`").complete(Completion::Values("profiles")))
+	    .opt(Opt::value("only", None, "target", "Germinate only the head or only the library, by name").complete(Completion::Values("targets")))
+	    .opt(Opt::value("rustc-arg", None, "arg", "Pass an argument to rustc after the mushroom's own, can be repeated"))
+	    .opt(Opt::flag("dry-run", Some('n'), "Print the rustc commands without compiling or touching the head"))
+	    .opt(Opt::flag("print-command", None, "Print the rustc commands while building"))
+	    .positional("dir", false, "Project folder, defaults to the current one").completes(Completion::Dirs)
+	    .trailing("run-args", "Passed onto the head ran with `-r`")
     }
 
-    return false;
-}
+    fn summary(&self) -> &'static str {
+	"Germinate! Compile rust based on kinoko.🍄"
+    }
 
-pub fn usage_message() -> (String, &'static str) {
-    (
-	format!("{} [-r] [dir] [-- [run-args]]", COMMAND_NAME),
-	"Germinate! Compile rust based on kinoko.🍄\n\t\t\t\t Pass `-r` flag to run build after compilation.\n\t\t\t\t Any arguments passed after `--` will be passed onto the ran build"
-    )
+    fn help(&self) -> &'static str {
+	"Compiles the mushroom's root into its head with rustc, its library first when it has one."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko build", "Germinate the mushroom of the current folder"),
+	    ("kinoko build -r -- --some-flag", "Germinate and run the head with `--some-flag`"),
+	    ("kinoko build --profile release path/to/project", "Germinate another project with its `profile.release` flags"),
+	    ("kinoko build --rustc-arg=-O --rustc-arg=-g", "Pass extra flags to rustc"),
+	    ("kinoko build --dry-run --profile release", "Print the rustc commands of the release profile, ready to paste in a shell"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to build"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
 }
 
-pub fn run_command(_cwd: PathBuf, mut args: Vec<String>) -> CmdResult {
-    // Remove command name
-    args.remove(0);
-    let run_build = if args.len() > 0 {
-	let mut run_flag = false;
-	for i in 0..args.len() {
-	    if args[i] == "-r" {
-		args.remove(i);
-		run_flag = true;
-		break;
-	    }
-	}
-	run_flag
-    } else {
-	false
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let run_build = args.flag("run");
+    let dir = match args.positional("dir") {
+	Some(dir) => ctx.cwd.join(dir),
+	None => ctx.cwd.clone(),
     };
-
-    let mut build_args = Vec::new();
-    let mut run_args = Vec::new();
-    let mut pass_to_run = false;
-    for arg in args.into_iter() {
-	if pass_to_run {
-	    run_args.push(arg);
-	    continue;
-	}
-	if arg == "--" && ! pass_to_run {
-	    pass_to_run = true;
-	    continue;
-	}
-	build_args.push(arg);
+    if !dir.is_dir() {
+	return Err(KinokoError::Args(format!("Not a directory: {}", dir.display())));
+    }
+    let mut kin = Kinoko::at(dir).context(ctx.clone())
+	.extra_args(args.values("rustc-arg"))
+	.print_commands(args.flag("print-command"));
+    if let Some(profile) = args.value("profile") {
+	kin = kin.profile(profile);
     }
-    
-    let kin = if build_args.len() > 0 {
-	let path = PathBuf::from(&build_args[0]);
-	if path.exists() && path.is_dir() {
-	    build_args.remove(0);
-	    Kinoko::new_with_args(path, build_args)
-	} else {
-	    Kinoko::new_with_args((if cfg!(windows) { ".\\" } else { "./" }).into(), build_args)
+    if let Some(target) = args.value("only") {
+	kin = kin.only(target);
+    }
+    let kin = kin.build();
+    if run_build && kin.only.is_some() && kin.lib_only(&kin.pick_mushroom()?)? {
+	return Err(KinokoError::Args(String::from("The library can't be ran, drop -r or --only")));
+    }
+    if args.flag("dry-run") {
+	for cmd in kin.germination_commands()? {
+	    println!("{}", command_line(&cmd));
 	}
-    } else {
-	Kinoko::new_with_args((if cfg!(windows) { ".\\" } else { "./" }).into(), build_args)
-    };
+	return Ok(());
+    }
+    let run_args = args.trailing;
 
-    return match kin.try_germinate() {
-	Err(err) => Err(format!("{}", err)),
+    let germinated = kin.try_germinate();
+    // After germinating, the build script output it embeds is the one just produced
+    refresh_rust_project(ctx, &kin);
+    return match germinated {
+	Err(err) => Err(KinokoError::from(err)),
 	Ok(out) => {
 	    if run_build {
 		let mut command = format!("{}", out.display());
@@ -83,20 +103,20 @@ pub fn run_command(_cwd: PathBuf, mut args: Vec<String>) -> CmdResult {
 		    command = format!("{} `{}`", command, arg);
 		    cmd.arg(arg);
 		}
-		println!("[CMD] {}", command);
+		info!(ctx, "Running {}", command);
 		let result = cmd.status();
 		if let Ok(status) = result {
-		    info!("Build output is executable.");
+		    verbose!(ctx, "Build output is executable.");
 		    if status.success() {
-			info!("{} - Exited with a success", out.display());
+			info!(ctx, "{} - Exited with a success", out.display());
 		    } else {
 			match status.code() {
-			    Some(code) => info!("{} - Exited with a failure result: {}", out.display(), code),
-			    None => info!("{} - Exited abruptly: Process terminated by a signal", out.display()),
+			    Some(code) => warn!(ctx, "{} - Exited with a failure result: {}", out.display(), code),
+			    None => warn!(ctx, "{} - Exited abruptly: Process terminated by a signal", out.display()),
 			};
 		    }
 		} else {
-		    error!("Build output was failed to be executed: {}", out.display());
+		    error!(ctx, "Build output was failed to be executed: {}", out.display());
 		}
 	    }
 
@@ -104,3 +124,18 @@ pub fn run_command(_cwd: PathBuf, mut args: Vec<String>) -> CmdResult {
 	},
     };
 }
+
+/// Keeps an existing rust-project.json in step with the mushroom, a failure doesn't stop the build
+fn refresh_rust_project(ctx: &Context, kin: &Kinoko) {
+    if !rust_project::is_stale(kin) {
+	return;
+    }
+    let written = match kin.pick_mushroom() {
+	Ok(mushroom) => rust_project::write_rust_project(kin, &mushroom).map_err(|err| err.to_string()),
+	Err(err) => Err(err.to_string()),
+    };
+    match written {
+	Ok(path) => verbose!(ctx, "Updated {}", path.display()),
+	Err(err) => warn!(ctx, "Failed to update {}: {}", rust_project::RUST_PROJECT_FILE, err),
+    }
+}
@@ -0,0 +1,318 @@
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use subcommand::{Subcommand, Registry, command_spec, global_options};
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use templates;
+use plugins;
+use cmd_man;
+use commands;
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "completions";
+const SHELLS: [&'static str; 3] = ["bash", "zsh", "fish"];
+
+pub struct CompletionsCommand;
+
+impl Subcommand for CompletionsCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::value("values", None, "list", "Print the values of `profiles`, `targets`, `templates`, `plugins` or `pages`, used by the scripts while completing"))
+	    .positional("shell", false, "One of bash, zsh or fish").completes(Completion::Words(&SHELLS))
+    }
+
+    fn summary(&self) -> &'static str {
+	"Print the shell completion script for bash, zsh or fish"
+    }
+
+    fn help(&self) -> &'static str {
+	"Commands and options are completed from kinoko itself, profiles and targets come from the kinoko.🍄 of the folder being completed in.
+Load them from your shell's config, for example:
+  bash: source <(kinoko completions bash)
+  zsh:  source <(kinoko completions zsh)
+  fish: kinoko completions fish | source"
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("source <(kinoko completions bash)", "Load the bash completions, add it to ~/.bashrc to keep them"),
+	    ("kinoko completions fish | source", "Load the fish completions"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to generate completions"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    if let Some(list) = args.value("values") {
+	for value in dynamic_values(ctx, list)? {
+	    println!("{}", value);
+	}
+	return Ok(());
+    }
+    let registry = commands();
+    let script = match args.positional("shell") {
+	Some("bash") => bash_script(&registry),
+	Some("zsh") => zsh_script(&registry),
+	Some("fish") => fish_script(&registry),
+	Some(other) => return Err(KinokoError::Args(format!("Unknown shell `{}`, expected one of: {}", other, SHELLS.join(", ")))),
+	None => return Err(KinokoError::Args(format!("Missing shell, expected one of: {}", SHELLS.join(", ")))),
+    };
+    print!("{}", script);
+    Ok(())
+}
+
+/// Lists read while completing, so they follow the project and the user's setup
+fn dynamic_values(ctx: &Context, list: &str) -> Result<Vec<String>, KinokoError> {
+    return match list {
+	"profiles" | "targets" => {
+	    let kin = Kinoko::at(ctx.cwd.clone()).context(ctx.clone()).build();
+	    if !kin.has_roots_at_cwd() {
+		return Ok(Vec::new());
+	    }
+	    let mushroom = kin.pick_mushroom()?;
+	    if list == "targets" {
+		return Ok(mushroom.target_names());
+	    }
+	    Ok(mushroom.profiles.into_iter().map(|(name, _)| name).collect())
+	},
+	"templates" => Ok(templates::list_templates().into_iter().map(|(name, _)| name).collect()),
+	"plugins" => Ok(plugins::list_plugins().into_iter().map(|(name, _)| name).collect()),
+	"pages" => Ok(cmd_man::page_names(&commands()).into_iter().map(|(name, _)| name).collect()),
+	_ => Err(KinokoError::Args(format!("Unknown list `{}`, expected profiles, targets, templates, plugins or pages", list))),
+    };
+}
+
+/// `build|build-run`
+fn command_names(cmd: &dyn Subcommand) -> Vec<&'static str> {
+    let mut names = vec![cmd.name()];
+    names.extend(cmd.aliases().iter());
+    return names;
+}
+
+fn all_command_names(registry: &Registry) -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = registry.iter().map(|cmd| cmd.name()).collect();
+    names.push("help");
+    return names;
+}
+
+fn bash_compgen(complete: Completion) -> Option<String> {
+    return match complete {
+	Completion::Nothing => None,
+	Completion::Dirs => Some(String::from("$(compgen -d -- \"$cur\")")),
+	Completion::Words(words) => Some(format!("$(compgen -W \"{}\" -- \"$cur\")", words.join(" "))),
+	Completion::Values(list) => Some(format!("$(compgen -W \"$(_kinoko_values {})\" -- \"$cur\")", list)),
+    };
+}
+
+/// `case "$prev"` arms completing the values of options, then flags or positionals and the `extra` replies
+fn bash_arguments(spec: &ArgSpec, extra: Option<String>, indent: &str) -> String {
+    let mut script = String::new();
+    script.push_str(&format!("{}case \"$prev\" in\n", indent));
+    for opt in spec.options.iter().filter(|opt| opt.value.is_some()) {
+	let pattern = match opt.short {
+	    Some(short) => format!("-{}|--{}", short, opt.long),
+	    None => format!("--{}", opt.long),
+	};
+	let reply = match bash_compgen(opt.complete) {
+	    Some(compgen) => format!("COMPREPLY=({}); return ;;", compgen),
+	    None => String::from("return ;;"),
+	};
+	script.push_str(&format!("{}    {}) {}\n", indent, pattern, reply));
+    }
+    script.push_str(&format!("{}esac\n", indent));
+
+    let mut flags = Vec::new();
+    for opt in spec.options.iter() {
+	if let Some(short) = opt.short {
+	    flags.push(format!("-{}", short));
+	}
+	flags.push(format!("--{}", opt.long));
+    }
+    let mut positionals: Vec<String> = spec.positionals.iter().filter_map(|positional| bash_compgen(positional.complete)).collect();
+    positionals.extend(extra);
+    script.push_str(&format!("{}if [[ \"$cur\" == -* ]]; then\n", indent));
+    script.push_str(&format!("{}    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", indent, flags.join(" ")));
+    if !positionals.is_empty() {
+	script.push_str(&format!("{}else\n", indent));
+	script.push_str(&format!("{}    COMPREPLY=({})\n", indent, positionals.join(" ")));
+    }
+    script.push_str(&format!("{}fi\n", indent));
+    return script;
+}
+
+fn bash_script(registry: &Registry) -> String {
+    let commands = all_command_names(registry).join(" ");
+    let mut global_spec = ArgSpec::new();
+    global_spec.options = global_options();
+    let global_values: Vec<String> = global_spec.options.iter()
+	.filter(|opt| opt.value.is_some())
+	.map(|opt| format!("--{}", opt.long))
+	.collect();
+
+    let mut script = String::from("# bash completion for kinoko, generated by `kinoko completions bash`\n\n");
+    script.push_str("_kinoko_values() {\n    kinoko completions --values \"$1\" 2>/dev/null\n}\n\n");
+    script.push_str("_kinoko() {\n");
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    local cmd=\"\" i\n");
+    script.push_str("    COMPREPLY=()\n");
+    script.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    script.push_str(&format!("        case \"${{COMP_WORDS[i-1]}}\" in\n            {}) continue ;;\n        esac\n", global_values.join("|")));
+    script.push_str("        case \"${COMP_WORDS[i]}\" in\n            -*) ;;\n            *) cmd=\"${COMP_WORDS[i]}\"; break ;;\n        esac\n");
+    script.push_str("    done\n\n");
+    script.push_str("    case \"$cmd\" in\n");
+    script.push_str("        \"\")\n");
+    let command_words = format!("$(compgen -W \"{} $(_kinoko_values plugins)\" -- \"$cur\")", commands);
+    script.push_str(&bash_arguments(&global_spec, Some(command_words.clone()), "            "));
+    script.push_str("            ;;\n");
+    script.push_str(&format!("        help)\n            COMPREPLY=({})\n            ;;\n", command_words));
+    for cmd in registry.iter() {
+	script.push_str(&format!("        {})\n", command_names(cmd).join("|")));
+	script.push_str(&bash_arguments(&command_spec(cmd), None, "            "));
+	script.push_str("            ;;\n");
+    }
+    // Plugins take whatever they want
+    script.push_str("        *)\n            COMPREPLY=($(compgen -f -- \"$cur\"))\n            ;;\n");
+    script.push_str("    esac\n}\n\n");
+    script.push_str("complete -F _kinoko kinoko\n");
+    return script;
+}
+
+/// Quotes text for a single quoted zsh word
+fn zsh_quote(text: &str) -> String {
+    return text.replace('\'', "'\\''");
+}
+
+/// Option descriptions sit between `[` and `]`
+fn zsh_description(text: &str) -> String {
+    return zsh_quote(&text.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]"));
+}
+
+fn zsh_action(complete: Completion) -> String {
+    return match complete {
+	Completion::Nothing => String::from(" "),
+	Completion::Dirs => String::from("_files -/"),
+	Completion::Words(words) => format!("({})", words.join(" ")),
+	Completion::Values(list) => format!("_kinoko_values {}", list),
+    };
+}
+
+fn zsh_option_specs(options: &Vec<Opt>) -> Vec<String> {
+    let mut specs = Vec::new();
+    for opt in options.iter() {
+	let names = match opt.short {
+	    Some(short) => format!("{{-{},--{}}}", short, opt.long),
+	    None => format!("--{}", opt.long),
+	};
+	let value = match opt.value {
+	    Some(value) => format!(":{}:{}", zsh_quote(value), zsh_quote(&zsh_action(opt.complete))),
+	    None => String::new(),
+	};
+	// Repeatable options like `-v` are offered again
+	specs.push(format!("'*'{}'[{}]{}'", names, zsh_description(opt.help), value));
+    }
+    return specs;
+}
+
+fn zsh_script(registry: &Registry) -> String {
+    let mut script = String::from("#compdef kinoko\n# zsh completion for kinoko, generated by `kinoko completions zsh`\n\n");
+    script.push_str("_kinoko_values() {\n    local -a values\n    values=(${(f)\"$(kinoko completions --values $1 2>/dev/null)\"})\n    compadd -a values\n}\n\n");
+    script.push_str("_kinoko() {\n");
+    script.push_str("    local curcontext=\"$curcontext\" state line\n");
+    script.push_str("    local -a commands\n    commands=(\n");
+    for cmd in registry.iter() {
+	script.push_str(&format!("        '{}:{}'\n", cmd.name(), zsh_quote(cmd.summary())));
+    }
+    script.push_str("        'help:Display the help of kinoko or a command'\n    )\n\n");
+    script.push_str("    _arguments -C \\\n");
+    for spec in zsh_option_specs(&global_options()).iter() {
+	script.push_str(&format!("        {} \\\n", spec));
+    }
+    script.push_str("        '1: :->command' \\\n        '*:: :->args'\n\n");
+    script.push_str("    case $state in\n");
+    script.push_str("        command)\n            _describe -t commands 'kinoko command' commands\n            _kinoko_values plugins\n            ;;\n");
+    script.push_str("        args)\n            case $words[1] in\n");
+    script.push_str("                help)\n                    _describe -t commands 'kinoko command' commands\n                    ;;\n");
+    for cmd in registry.iter() {
+	let spec = command_spec(cmd);
+	let mut specs = zsh_option_specs(&spec.options);
+	for (idx, positional) in spec.positionals.iter().enumerate() {
+	    specs.push(format!("'{}:{}:{}'", idx + 1, zsh_quote(positional.name), zsh_quote(&zsh_action(positional.complete))));
+	}
+	script.push_str(&format!("                {})\n                    _arguments \\\n", command_names(cmd).join("|")));
+	script.push_str(&specs.iter().map(|spec| format!("                        {}", spec)).collect::<Vec<String>>().join(" \\\n"));
+	script.push_str("\n                    ;;\n");
+    }
+    script.push_str("                *)\n                    _files\n                    ;;\n");
+    script.push_str("            esac\n            ;;\n    esac\n}\n\n");
+    script.push_str("if [ \"$funcstack[1]\" = \"_kinoko\" ]; then\n    _kinoko \"$@\"\nelse\n    compdef _kinoko kinoko\nfi\n");
+    return script;
+}
+
+/// Quotes text for a single quoted fish word
+fn fish_quote(text: &str) -> String {
+    return format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+}
+
+/// Arguments of `complete` for a value or positional
+fn fish_values(complete: Completion) -> String {
+    return match complete {
+	Completion::Nothing => String::new(),
+	Completion::Dirs => String::from(" -a '(__fish_complete_directories)'"),
+	Completion::Words(words) => format!(" -a {}", fish_quote(&words.join(" "))),
+	Completion::Values(list) => format!(" -a '(kinoko completions --values {} 2>/dev/null)'", list),
+    };
+}
+
+fn fish_option(condition: &str, opt: &Opt) -> String {
+    let mut line = format!("complete -c kinoko{}", condition);
+    if let Some(short) = opt.short {
+	line.push_str(&format!(" -s {}", short));
+    }
+    line.push_str(&format!(" -l {}", opt.long));
+    if opt.value.is_some() {
+	line.push_str(" -x");
+	line.push_str(&fish_values(opt.complete));
+    }
+    line.push_str(&format!(" -d {}\n", fish_quote(opt.help)));
+    return line;
+}
+
+fn fish_script(registry: &Registry) -> String {
+    let mut script = String::from("# fish completion for kinoko, generated by `kinoko completions fish`\n\n");
+    script.push_str("complete -c kinoko -f\n");
+    for opt in global_options().iter() {
+	script.push_str(&fish_option(" -n __fish_use_subcommand", opt));
+    }
+    for cmd in registry.iter() {
+	script.push_str(&format!("complete -c kinoko -n __fish_use_subcommand -a {} -d {}\n", cmd.name(), fish_quote(cmd.summary())));
+    }
+    script.push_str("complete -c kinoko -n __fish_use_subcommand -a help -d 'Display the help of kinoko or a command'\n");
+    script.push_str("complete -c kinoko -n __fish_use_subcommand -a '(kinoko completions --values plugins 2>/dev/null)' -d Plugin\n");
+    script.push_str(&format!("complete -c kinoko -n '__fish_seen_subcommand_from help' -a {}\n", fish_quote(&all_command_names(registry).join(" "))));
+    for cmd in registry.iter() {
+	let condition = format!(" -n '__fish_seen_subcommand_from {}'", command_names(cmd).join(" "));
+	let spec = command_spec(cmd);
+	for opt in spec.options.iter() {
+	    script.push_str(&fish_option(&condition, opt));
+	}
+	for positional in spec.positionals.iter() {
+	    let values = fish_values(positional.complete);
+	    if !values.is_empty() {
+		script.push_str(&format!("complete -c kinoko{}{}\n", condition, values));
+	    }
+	}
+    }
+    return script;
+}
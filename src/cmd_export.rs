@@ -0,0 +1,296 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use kinoko::build_script;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use kinoko::utility::{info, warn};
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "export";
+
+pub struct ExportCommand;
+
+impl Subcommand for ExportCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::flag("force", Some('f'), "Overwrite an existing Cargo.toml"))
+	    .positional("format", true, "Only `cargo` is supported").completes(Completion::Words(&["cargo"]))
+	    .positional("dir", false, "Folder of the mushroom, defaults to the current one").completes(Completion::Dirs)
+    }
+
+    fn summary(&self) -> &'static str {
+	"Write a Cargo.toml describing the mushroom, sources stay in place"
+    }
+
+    fn help(&self) -> &'static str {
+	"The package and binary are named after the head and the edition, features and profiles are carried over.
+Profile flags cargo has no setting for are left as comments."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko export cargo", "Write a Cargo.toml for the current project"),
+	    ("kinoko export cargo -f path/to/project", "Replace the Cargo.toml of another project"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to export"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let format = args.positional("format").unwrap_or_default();
+    if format != "cargo" {
+	return Err(KinokoError::Args(format!("Unknown export format `{}`, only `cargo` is supported", format)));
+    }
+    let force = args.flag("force");
+    let dir = match args.positional("dir") {
+	Some(dir) => ctx.cwd.join(dir),
+	None => ctx.cwd.clone(),
+    };
+
+    let kin = Kinoko::at(dir).context(ctx.clone()).build();
+    let mushroom = kin.pick_mushroom()?;
+    let manifest_path = kin.cwd.join("Cargo.toml");
+    if manifest_path.exists() && !force {
+	return Err(KinokoError::Other(format!("{} already exists, pass --force to overwrite it", manifest_path.display())));
+    }
+
+    let manifest = cargo_manifest(ctx, &mushroom).map_err(KinokoError::Other)?;
+    if let Err(err) = fs::write(&manifest_path, manifest) {
+	return Err(KinokoError::io_at("write", &manifest_path, err));
+    }
+    info!(ctx, "Exported mushroom to {}", manifest_path.display());
+    let mut needs_changes = false;
+    if !mushroom.pre_build.is_empty() || !mushroom.post_build.is_empty() {
+	warn!(ctx, "Cargo has no pre-build or post-build hooks, run them yourself around `cargo build`");
+	needs_changes = true;
+    }
+    if let Some(script) = build_script::find_build_script(&kin, &mushroom) {
+	if fs::read_to_string(&script).map(|source| source.contains("kinoko:")).unwrap_or(false) {
+	    warn!(ctx, "Cargo runs {} but ignores its `kinoko:` directives, print `cargo:` ones like `cargo:rustc-cfg=...` for it", script.display());
+	    needs_changes = true;
+	}
+    }
+    for (name, file) in kinoko_env_uses(&kin, &mushroom).iter() {
+	match name.strip_prefix("KINOKO_PKG_") {
+	    Some(field) => warn!(ctx, "{} reads {}, cargo doesn't set it, use CARGO_PKG_{} instead", file.display(), name, field),
+	    None => warn!(ctx, "{} reads {}, cargo doesn't set it, a build.rs printing `cargo:rustc-env={}=...` can", file.display(), name, name),
+	}
+	needs_changes = true;
+    }
+    if needs_changes {
+	info!(ctx, "Once the warnings above are dealt with, build it with `cargo build`, the output will be in target/ instead of {}", mushroom.head);
+    } else {
+	info!(ctx, "Build it with `cargo build`, the output will be in target/ instead of {}", mushroom.head);
+    }
+    Ok(())
+}
+
+/// The variables only kinoko sets (`KINOKO_PKG_*`, `KINOKO_GIT_*`) the sources read, with the first file reading each
+fn kinoko_env_uses(kin: &Kinoko, mushroom: &Mushroom) -> Vec<(String, PathBuf)> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for root in Some(&mushroom.root).into_iter().chain(mushroom.lib.iter()) {
+	if let Some(dir) = kin.cwd.join(root).parent() {
+	    if !dirs.iter().any(|known| dir.starts_with(known)) {
+		dirs.push(dir.to_path_buf());
+	    }
+	}
+    }
+    let mut files = Vec::new();
+    for dir in dirs.iter() {
+	collect_rust_files(dir, &mut files, 0);
+    }
+    files.sort();
+    let mut uses: Vec<(String, PathBuf)> = Vec::new();
+    for file in files.iter() {
+	let source = match fs::read_to_string(file) {
+	    Ok(source) => source,
+	    Err(_) => continue,
+	};
+	for prefix in ["KINOKO_PKG_", "KINOKO_GIT_"].iter() {
+	    for (idx, _) in source.match_indices(prefix) {
+		let name: String = source[idx..].chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect();
+		if !uses.iter().any(|(known, _)| *known == name) {
+		    uses.push((name, file.clone()));
+		}
+	    }
+	}
+    }
+    return uses;
+}
+
+fn collect_rust_files(dir: &Path, files: &mut Vec<PathBuf>, recursion_level: usize) {
+    if recursion_level > 8 {
+	return;
+    }
+    let entries = match fs::read_dir(dir) {
+	Ok(entries) => entries,
+	Err(_) => return,
+    };
+    for entry in entries.flatten() {
+	let path = entry.path();
+	if path.is_dir() {
+	    collect_rust_files(&path, files, recursion_level + 1);
+	} else if path.extension().map(|ext| ext == "rs").unwrap_or(false) {
+	    files.push(path);
+	}
+    }
+}
+
+fn cargo_manifest(ctx: &Context, mushroom: &Mushroom) -> Result<String, String> {
+    let head_name = match Path::new(&mushroom.head).file_name() {
+	Some(name) => name.to_string_lossy().to_string(),
+	None => return Err(format!("Mushroom head has no name: {}", mushroom.head)),
+    };
+    let package_name: String = mushroom.get_package_name().unwrap_or(head_name.clone()).chars()
+	.map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+	.collect();
+
+    let mut toml = String::new();
+    toml.push_str("[package]\n");
+    toml.push_str(&format!("name = {}\n", toml_string(&package_name)));
+    toml.push_str(&format!("version = {}\n", toml_string(mushroom.version.as_ref().map(|v| v.as_str()).unwrap_or("0.1.0"))));
+    if !mushroom.authors.is_empty() {
+	let quoted: Vec<String> = mushroom.authors.iter().map(|author| toml_string(author)).collect();
+	toml.push_str(&format!("authors = [{}]\n", quoted.join(", ")));
+    }
+    if let Some(description) = &mushroom.description {
+	toml.push_str(&format!("description = {}\n", toml_string(description)));
+    }
+    // rustc defaults to 2015 when no edition is given, keep it so the sources compile the same
+    toml.push_str(&format!("edition = {}\n", toml_string(mushroom.edition.as_ref().map(|e| e.as_str()).unwrap_or("2015"))));
+    if let Some(rust_version) = &mushroom.rust_version {
+	toml.push_str(&format!("rust-version = {}\n", toml_string(rust_version)));
+    }
+    if let Some(script) = &mushroom.build_script {
+	toml.push_str(&format!("build = {}\n", toml_string(&script.replace('\\', "/"))));
+    }
+    if let (Some(lib), Some(lib_name)) = (&mushroom.lib, mushroom.get_lib_name()) {
+	toml.push_str("\n[lib]\n");
+	toml.push_str(&format!("name = {}\n", toml_string(&lib_name)));
+	toml.push_str(&format!("path = {}\n", toml_string(&lib.replace('\\', "/"))));
+    }
+    toml.push_str("\n[[bin]]\n");
+    toml.push_str(&format!("name = {}\n", toml_string(&head_name)));
+    toml.push_str(&format!("path = {}\n", toml_string(&mushroom.root.replace('\\', "/"))));
+
+    if !mushroom.features.is_empty() {
+	toml.push_str("\n[features]\n");
+	let quoted: Vec<String> = mushroom.features.iter().map(|f| toml_string(f)).collect();
+	toml.push_str(&format!("default = [{}]\n", quoted.join(", ")));
+	for feature in mushroom.features.iter() {
+	    toml.push_str(&format!("{} = []\n", toml_key(feature)));
+	}
+    }
+
+    for (name, flags) in mushroom.profiles.iter() {
+	toml.push_str(&format!("\n[profile.{}]\n", toml_key(name)));
+	if !["dev", "release", "test", "bench"].contains(&name.as_str()) {
+	    toml.push_str("inherits = \"dev\"\n");
+	}
+	for line in profile_settings(ctx, name, flags).iter() {
+	    toml.push_str(line);
+	    toml.push('\n');
+	}
+    }
+    return Ok(toml);
+}
+
+/// Translates rustc flags into cargo profile settings, flags cargo can't express are left as comments
+fn profile_settings(ctx: &Context, profile: &str, flags: &Vec<String>) -> Vec<String> {
+    let mut settings = Vec::new();
+    let mut idx = 0;
+    while idx < flags.len() {
+	let flag = &flags[idx];
+	idx += 1;
+	let codegen = if flag == "-C" || flag == "--codegen" {
+	    match flags.get(idx) {
+		Some(value) => {
+		    idx += 1;
+		    Some(value.clone())
+		},
+		None => None,
+	    }
+	} else if let Some(value) = flag.strip_prefix("-C") {
+	    Some(value.to_string())
+	} else {
+	    None
+	};
+	let setting = match (flag.as_str(), &codegen) {
+	    ("-O", _) => Some(String::from("opt-level = 2")),
+	    ("-g", _) => Some(String::from("debug = true")),
+	    (_, Some(option)) => codegen_setting(option),
+	    _ => None,
+	};
+	match setting {
+	    Some(setting) => settings.push(setting),
+	    None => {
+		let original = match &codegen {
+		    Some(option) => format!("-C {}", option),
+		    None => flag.clone(),
+		};
+		warn!(ctx, "Profile `{}` flag `{}` has no cargo equivalent, left as a comment", profile, original);
+		settings.push(format!("# kinoko flag without cargo equivalent: {}", original));
+	    },
+	}
+    }
+    return settings;
+}
+
+fn codegen_setting(option: &str) -> Option<String> {
+    let (key, value) = match option.split_once('=') {
+	Some((key, value)) => (key, Some(value)),
+	None => (option, None),
+    };
+    let as_bool = |value: Option<&str>| -> Option<&'static str> {
+	match value {
+	    None | Some("y") | Some("yes") | Some("on") | Some("true") => Some("true"),
+	    Some("n") | Some("no") | Some("off") | Some("false") => Some("false"),
+	    _ => None,
+	}
+    };
+    let as_number_or_string = |value: &str| -> String {
+	if value.parse::<u32>().is_ok() { value.to_string() } else { toml_string(value) }
+    };
+    return match key {
+	"opt-level" => value.map(|v| format!("opt-level = {}", as_number_or_string(v))),
+	"debuginfo" => value.map(|v| format!("debug = {}", as_number_or_string(v))),
+	"codegen-units" => value.map(|v| format!("codegen-units = {}", v)),
+	"panic" => value.map(|v| format!("panic = {}", toml_string(v))),
+	"strip" => value.map(|v| format!("strip = {}", toml_string(v))),
+	"lto" => match value {
+	    Some("thin") | Some("fat") => value.map(|v| format!("lto = {}", toml_string(v))),
+	    _ => as_bool(value).map(|v| format!("lto = {}", v)),
+	},
+	"overflow-checks" => as_bool(value).map(|v| format!("overflow-checks = {}", v)),
+	"debug-assertions" => as_bool(value).map(|v| format!("debug-assertions = {}", v)),
+	"rpath" => as_bool(value).map(|v| format!("rpath = {}", v)),
+	"incremental" => Some(String::from("incremental = true")),
+	_ => None,
+    };
+}
+
+fn toml_string(value: &str) -> String {
+    return format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+	return key.to_string();
+    }
+    return toml_string(key);
+}
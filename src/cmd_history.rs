@@ -0,0 +1,66 @@
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use subcommand::Subcommand;
+use args::{ArgSpec, ParsedArgs};
+use kinoko::head_history;
+use kinoko::utility::format_unix_timestamp;
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "history";
+
+pub struct HistoryCommand;
+
+impl Subcommand for HistoryCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+    }
+
+    fn summary(&self) -> &'static str {
+	"List previously germinated heads, newest first"
+    }
+
+    fn help(&self) -> &'static str {
+	"Heads are kept in a `.history/` folder next to the head, `history: <n>` in kinoko.🍄 sets how many.
+A `*` marks the heads matching the current head."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko history", "List the kept heads of the current project"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to list history"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, _args: ParsedArgs) -> CmdResult {
+    let kin = Kinoko::at(ctx.cwd.clone()).context(ctx.clone()).build();
+    let mushroom = kin.pick_mushroom()?;
+    let heads = match head_history::list_heads(&mushroom, &kin) {
+	Ok(heads) => heads,
+	Err(err) => return Err(KinokoError::io("Failed to read head history", err)),
+    };
+    if heads.is_empty() {
+	println!("No heads in history yet, germinate to start one");
+	return Ok(());
+    }
+
+    let head_path = kin.get_mushroom_head_path(&mushroom);
+    for (idx, head) in heads.iter().enumerate() {
+	let marker = if head.matches_file(&head_path) { "*" } else { " " };
+	println!("{} {:>3}  {}  {}  {}", marker, idx, format_unix_timestamp(head.timestamp), head.fingerprint, head.path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use kinoko::rust_project;
+use kinoko::utility::info;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, ParsedArgs};
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "ide";
+
+pub struct IdeCommand;
+
+impl Subcommand for IdeCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .positional("dir", false, "Project folder, defaults to the current one").completes(Completion::Dirs)
+    }
+
+    fn summary(&self) -> &'static str {
+	"Write a rust-project.json so rust-analyzer understands the mushroom"
+    }
+
+    fn help(&self) -> &'static str {
+	"The crates, edition, features and the library the head uses come from kinoko.🍄, the standard library from `rustc --print sysroot`.
+Once it exists, `build` writes it again whenever kinoko.🍄, the rust-toolchain file or the build script output changes."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko ide", "Write the rust-project.json of the current project"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to write rust-project.json"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let dir = match args.positional("dir") {
+	Some(dir) => ctx.cwd.join(dir),
+	None => ctx.cwd.clone(),
+    };
+    let kin = Kinoko::at(dir).context(ctx.clone()).build();
+    let mushroom = kin.pick_mushroom()?;
+    return match rust_project::write_rust_project(&kin, &mushroom) {
+	Ok(path) => {
+	    info!(ctx, "Wrote {}", path.display());
+	    Ok(())
+	},
+	Err(err) => Err(KinokoError::io(format!("Failed to write {}", rust_project::rust_project_path(&kin).display()), err)),
+    };
+}
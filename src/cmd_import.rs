@@ -0,0 +1,501 @@
+use std::path::Path;
+use std::fs;
+
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use toml_subset::{self, TomlDocument, TomlValue};
+use kinoko::utility::{info, warn, error};
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "import";
+
+pub struct ImportCommand;
+
+impl Subcommand for ImportCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::flag("force", Some('f'), "Overwrite an existing kinoko.🍄"))
+	    .positional("format", true, "Only `cargo` is supported").completes(Completion::Words(&["cargo"]))
+	    .positional("dir", false, "Folder of the cargo package, defaults to the current one").completes(Completion::Dirs)
+    }
+
+    fn summary(&self) -> &'static str {
+	"Write a kinoko.🍄 from a Cargo.toml that only uses the standard library"
+    }
+
+    fn help(&self) -> &'static str {
+	"The binary becomes the root and head, `[lib]` becomes `lib` and the edition, default features and profiles become rustc flags.
+Anything kinoko can't germinate, like dependencies or native links, is listed and nothing is written.
+A build.rs stays the build script, the `cargo:` directives it prints have to become `kinoko:` ones."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko import cargo", "Write a kinoko.🍄 from the Cargo.toml of the current folder"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to import"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let format = args.positional("format").unwrap_or_default();
+    if format != "cargo" {
+	return Err(KinokoError::Args(format!("Unknown import format `{}`, only `cargo` is supported", format)));
+    }
+    let force = args.flag("force");
+    let dir = match args.positional("dir") {
+	Some(dir) => ctx.cwd.join(dir),
+	None => ctx.cwd.clone(),
+    };
+
+    let manifest_path = dir.join("Cargo.toml");
+    let source = match fs::read_to_string(&manifest_path) {
+	Ok(source) => source,
+	Err(err) => return Err(KinokoError::io_at("read", &manifest_path, err)),
+    };
+    let doc = match toml_subset::parse(&source) {
+	Ok(doc) => doc,
+	Err(err) => return Err(KinokoError::Other(format!("Failed to parse {}: {}", manifest_path.display(), err))),
+    };
+
+    let mut report = ImportReport { unsupported: Vec::new(), ignored: Vec::new() };
+    let mushroom = mushroom_from_manifest(&doc, &dir, &mut report);
+    for msg in report.ignored.iter() {
+	warn!(ctx, "Ignored: {}", msg);
+    }
+    if !report.unsupported.is_empty() {
+	for msg in report.unsupported.iter() {
+	    error!(ctx, "Unsupported: {}", msg);
+	}
+	return Err(KinokoError::Other(format!("{} can't be germinated by kinoko without changes", manifest_path.display())));
+    }
+    let mushroom = match mushroom {
+	Some(mushroom) => mushroom,
+	None => return Err(KinokoError::Other("Found no binary target to use as the mushroom's root".to_string())),
+    };
+
+    let kin = Kinoko::at(dir).context(ctx.clone()).build();
+    let mushroom_path = kin.get_mushroom_path();
+    if mushroom_path.exists() && !force {
+	return Err(KinokoError::Other(format!("{} already exists, pass --force to overwrite it", mushroom_path.display())));
+    }
+    if let Err(err) = fs::write(&mushroom_path, mushroom.serialize()) {
+	return Err(KinokoError::io_at("write", &mushroom_path, err));
+    }
+    info!(ctx, "Imported {} into {}", manifest_path.display(), mushroom_path.display());
+    Ok(())
+}
+
+/// Problems found while reading the manifest, unsupported ones stop the import
+struct ImportReport {
+    unsupported: Vec<String>,
+    ignored: Vec<String>,
+}
+
+fn mushroom_from_manifest(doc: &TomlDocument, dir: &Path, report: &mut ImportReport) -> Option<Mushroom> {
+    check_unsupported_tables(doc, report);
+
+    let package = match doc.table("package") {
+	Some(package) => package,
+	None => {
+	    report.unsupported.push("no [package] table, workspaces are not supported".to_string());
+	    return None;
+	},
+    };
+    let package_name = match package.get("name").and_then(|v| v.as_str()) {
+	Some(name) => name.to_string(),
+	None => {
+	    report.unsupported.push("package.name is missing or not a string".to_string());
+	    return None;
+	},
+    };
+    for (key, _) in package.entries.iter() {
+	if key.ends_with(".workspace") {
+	    report.unsupported.push(format!("package.{} is inherited from a workspace", key));
+	}
+	if key == "links" {
+	    report.unsupported.push("package.links needs a build script linking native code".to_string());
+	}
+    }
+    // Cargo's default build script is kinoko's too, only another path has to be spelled out
+    let build_script = match package.get("build") {
+	Some(TomlValue::String(path)) if path != "build.rs" => Some(path.clone()),
+	Some(TomlValue::Boolean(false)) => {
+	    if dir.join("build.rs").is_file() {
+		report.unsupported.push("package.build = false, but kinoko always runs the build.rs next to the kinoko.🍄".to_string());
+	    }
+	    None
+	},
+	_ => None,
+    };
+    let script_path = dir.join(build_script.as_ref().map(|path| path.as_str()).unwrap_or("build.rs"));
+    if package.get("build") != Some(&TomlValue::Boolean(false)) {
+	if let Ok(source) = fs::read_to_string(&script_path) {
+	    if source.contains("cargo:") {
+		report.ignored.push(format!("{} prints `cargo:` directives, kinoko only reads `kinoko:` ones like `kinoko:rustc-cfg=...`", script_path.strip_prefix(dir).unwrap_or(&script_path).display()));
+	    }
+	}
+    }
+
+    let mut mushroom = Mushroom::new();
+    mushroom.build_script = build_script;
+    if let Some(edition) = package.get("edition") {
+	match edition.as_str() {
+	    Some(edition) => mushroom.edition = Some(edition.to_string()),
+	    None => report.unsupported.push(format!("package.edition = {} is not a string", edition)),
+	}
+    }
+    if let Some(rust_version) = package.get("rust-version") {
+	match rust_version.as_str() {
+	    Some(rust_version) => mushroom.rust_version = Some(rust_version.to_string()),
+	    None => report.unsupported.push(format!("package.rust-version = {} is not a string", rust_version)),
+	}
+    }
+    if let Some(version) = package.get("version") {
+	match version.as_str() {
+	    Some(version) => mushroom.version = Some(version.to_string()),
+	    None => report.unsupported.push(format!("package.version = {} is not a string", version)),
+	}
+    }
+    if let Some(description) = package.get("description") {
+	match description.as_str() {
+	    Some(description) => mushroom.description = Some(description.to_string()),
+	    None => report.unsupported.push(format!("package.description = {} is not a string", description)),
+	}
+    }
+    if let Some(authors) = package.get("authors").and_then(|v| v.as_array()) {
+	mushroom.authors = authors.iter().filter_map(|author| author.as_str()).map(String::from).collect();
+    }
+    mushroom.features = enabled_features(doc, report);
+    mushroom.profiles = profiles(doc, report);
+
+    let crate_name = package_name.replace('-', "_");
+    if let Some(lib) = doc.table("lib") {
+	if let Some(types) = lib.get("crate-type").and_then(|v| v.as_array()) {
+	    for crate_type in types.iter() {
+		if crate_type.as_str() != Some("lib") && crate_type.as_str() != Some("rlib") {
+		    report.unsupported.push(format!("lib.crate-type {} can't be linked into the head", crate_type));
+		}
+	    }
+	}
+	if lib.get("proc-macro") == Some(&TomlValue::Boolean(true)) {
+	    report.unsupported.push("lib.proc-macro crates are not supported".to_string());
+	}
+	let path = lib.get("path").and_then(|v| v.as_str()).unwrap_or("src/lib.rs");
+	mushroom.lib = Some(path.to_string());
+	let name = lib.get("name").and_then(|v| v.as_str()).unwrap_or(&crate_name);
+	mushroom.lib_name = Some(name.to_string());
+    } else if dir.join("src").join("lib.rs").is_file() {
+	mushroom.lib = Some("src/lib.rs".to_string());
+	mushroom.lib_name = Some(crate_name.clone());
+    }
+
+    let bins = binary_targets(doc, dir, &package_name);
+    let default_run = package.get("default-run").and_then(|v| v.as_str()).unwrap_or(&package_name);
+    let chosen = match bins.iter().position(|(name, _)| name == default_run) {
+	Some(idx) => idx,
+	None if !bins.is_empty() => 0,
+	None => {
+	    report.unsupported.push("package has no binary target, kinoko only germinates executables".to_string());
+	    return None;
+	},
+    };
+    for (idx, (name, path)) in bins.iter().enumerate() {
+	if idx != chosen {
+	    report.ignored.push(format!("binary `{}` at {}, a mushroom has a single head", name, path));
+	}
+    }
+    let (bin_name, bin_path) = bins[chosen].clone();
+    mushroom.root = bin_path;
+    mushroom.head = format!("build/{}", bin_name);
+    // The head's name is the package name unless the binary is named differently
+    if bin_name != package_name {
+	mushroom.name = Some(package_name.clone());
+    }
+    // Head and library share the name kinoko would derive, no need to spell it out
+    if mushroom.lib_name.as_ref().map(|name| *name == bin_name.replace('-', "_")).unwrap_or(false) {
+	mushroom.lib_name = None;
+    }
+    return Some(mushroom);
+}
+
+const DEPENDENCY_TABLES: [&'static str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Index of the first `dependencies`, `dev-dependencies` or `build-dependencies` segment,
+/// wherever it is: `[target.'cfg(unix)'.dependencies.libc]` depends on libc as much as `[dependencies]` does
+fn dependency_segment(path: &[String]) -> Option<usize> {
+    return path.iter().position(|segment| DEPENDENCY_TABLES.contains(&segment.as_str()));
+}
+
+fn check_unsupported_tables(doc: &TomlDocument, report: &mut ImportReport) {
+    for table in doc.tables.iter() {
+	let header = table.header.as_str();
+	let first = table.path.first().map(|segment| segment.as_str()).unwrap_or("");
+	if first == "package" || first == "badges" {
+	    continue;
+	} else if first == "workspace" {
+	    report.unsupported.push(format!("[{}], workspaces are not supported", header));
+	} else if first == "patch" || first == "replace" {
+	    report.ignored.push(format!("[{}], there are no dependencies to patch", header));
+	} else if let Some(idx) = dependency_segment(&table.path) {
+	    let dependencies = if idx + 1 == table.path.len() {
+		table.entries.iter().map(|(name, _)| format!("[{}] {}", header, name)).collect()
+	    } else {
+		vec![format!("[{}]", header)]
+	    };
+	    report_dependencies(&table.path[idx], dependencies, report);
+	} else if table.is_array && (header == "example" || header == "test" || header == "bench") {
+	    report.ignored.push(format!("[[{}]] targets are not germinated", header));
+	} else {
+	    // Dotted keys like `dependencies.x = ...` or `dependencies.libc = ...` under `[target.'cfg(unix)']`
+	    for (key, _) in table.entries.iter() {
+		let mut path = table.path.clone();
+		path.extend(key.split('.').map(String::from));
+		if let Some(idx) = dependency_segment(&path) {
+		    let name = if header.is_empty() { key.clone() } else { format!("[{}] {}", header, key) };
+		    report_dependencies(&path[idx], vec![name], report);
+		}
+	    }
+	}
+    }
+}
+
+fn report_dependencies(kind: &str, dependencies: Vec<String>, report: &mut ImportReport) {
+    for dependency in dependencies.into_iter() {
+	if kind == "dependencies" {
+	    report.unsupported.push(format!("{}, only the standard library is available", dependency));
+	} else {
+	    report.ignored.push(format!("{}, kinoko doesn't run cargo tests and build scripts only get the standard library", dependency));
+	}
+    }
+}
+
+/// Features turned on by default, kinoko features are always enabled
+fn enabled_features(doc: &TomlDocument, report: &mut ImportReport) -> Vec<String> {
+    let table = match doc.table("features") {
+	Some(table) => table,
+	None => return Vec::new(),
+    };
+    let mut enabled: Vec<String> = Vec::new();
+    let mut pending: Vec<String> = match table.get("default").and_then(|v| v.as_array()) {
+	Some(values) => values.iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+	None => Vec::new(),
+    };
+    while let Some(feature) = pending.pop() {
+	if feature.starts_with("dep:") || feature.contains('/') {
+	    report.unsupported.push(format!("feature `{}` enables a dependency", feature));
+	    continue;
+	}
+	if enabled.contains(&feature) {
+	    continue;
+	}
+	if let Some(implied) = table.get(&feature).and_then(|v| v.as_array()) {
+	    pending.extend(implied.iter().filter_map(|v| v.as_str().map(String::from)));
+	}
+	enabled.push(feature);
+    }
+    let optional: Vec<&String> = table.entries.iter().map(|(k, _)| k).filter(|k| *k != "default" && !enabled.contains(k)).collect();
+    if !optional.is_empty() {
+	let names: Vec<&str> = optional.iter().map(|k| k.as_str()).collect();
+	report.ignored.push(format!("features off by default: {}", names.join(", ")));
+    }
+    enabled.sort();
+    return enabled;
+}
+
+/// Cargo profiles as rustc flags, `release` is always included so `--profile release` keeps working
+fn profiles(doc: &TomlDocument, report: &mut ImportReport) -> Vec<(String, Vec<String>)> {
+    let mut names: Vec<String> = doc.tables.iter()
+	.filter_map(|t| t.header.strip_prefix("profile.").map(String::from))
+	.filter(|name| !name.contains('.'))
+	.collect();
+    if !names.contains(&"release".to_string()) {
+	names.insert(0, "release".to_string());
+    }
+    let mut result = Vec::new();
+    for name in names.iter() {
+	let settings = resolve_profile(doc, name, report, 0);
+	let flags = settings.iter().flat_map(|(key, value)| vec!["-C".to_string(), format!("{}={}", key, value)]).collect();
+	result.push((name.clone(), flags));
+    }
+    return result;
+}
+
+fn resolve_profile(doc: &TomlDocument, name: &str, report: &mut ImportReport, depth: usize) -> Vec<(String, String)> {
+    let table = doc.table(&format!("profile.{}", name));
+    let inherits = table.and_then(|t| t.get("inherits")).and_then(|v| v.as_str());
+    // Same defaults cargo uses for its built-in profiles
+    let mut settings: Vec<(String, String)> = match (name, inherits) {
+	("release", _) | ("bench", _) => vec![("opt-level".to_string(), "3".to_string())],
+	("dev", _) | ("test", _) => vec![
+	    ("debuginfo".to_string(), "2".to_string()),
+	    ("debug-assertions".to_string(), "yes".to_string()),
+	    ("overflow-checks".to_string(), "yes".to_string()),
+	],
+	(_, Some(parent)) if depth < 8 => resolve_profile(doc, parent, report, depth + 1),
+	_ => Vec::new(),
+    };
+    let table = match table {
+	Some(table) => table,
+	None => return settings,
+    };
+    for (key, value) in table.entries.iter() {
+	let setting = match profile_flag(key, value) {
+	    Ok(Some(setting)) => setting,
+	    Ok(None) => continue,
+	    Err(msg) => {
+		if depth == 0 {
+		    report.ignored.push(format!("profile.{}.{} = {}, {}", name, key, value, msg));
+		}
+		continue;
+	    },
+	};
+	settings.retain(|(k, _)| *k != setting.0);
+	settings.push(setting);
+    }
+    return settings;
+}
+
+/// Codegen option for a cargo profile key, `Ok(None)` for keys with nothing to pass on
+fn profile_flag(key: &str, value: &TomlValue) -> Result<Option<(String, String)>, &'static str> {
+    let yes_no = |value: &TomlValue| -> Result<String, &'static str> {
+	match value {
+	    TomlValue::Boolean(true) => Ok("yes".to_string()),
+	    TomlValue::Boolean(false) => Ok("no".to_string()),
+	    _ => Err("expected a boolean"),
+	}
+    };
+    let option = match (key, value) {
+	("inherits", _) | ("incremental", _) => return Ok(None),
+	("opt-level", TomlValue::Integer(n)) => ("opt-level", n.to_string()),
+	("opt-level", TomlValue::String(s)) => ("opt-level", s.clone()),
+	("debug", TomlValue::Boolean(true)) => ("debuginfo", "2".to_string()),
+	("debug", TomlValue::Boolean(false)) => ("debuginfo", "0".to_string()),
+	("debug", TomlValue::Integer(n)) => ("debuginfo", n.to_string()),
+	("debug", TomlValue::String(s)) => ("debuginfo", s.clone()),
+	("lto", TomlValue::Boolean(true)) => ("lto", "fat".to_string()),
+	("lto", TomlValue::Boolean(false)) => ("lto", "off".to_string()),
+	("lto", TomlValue::String(s)) => ("lto", s.clone()),
+	("codegen-units", TomlValue::Integer(n)) => ("codegen-units", n.to_string()),
+	("panic", TomlValue::String(s)) => ("panic", s.clone()),
+	("strip", TomlValue::Boolean(true)) => ("strip", "symbols".to_string()),
+	("strip", TomlValue::Boolean(false)) => ("strip", "none".to_string()),
+	("strip", TomlValue::String(s)) => ("strip", s.clone()),
+	("overflow-checks", v) => ("overflow-checks", yes_no(v)?),
+	("debug-assertions", v) => ("debug-assertions", yes_no(v)?),
+	("rpath", v) => ("rpath", yes_no(v)?),
+	_ => return Err("no rustc flag kinoko knows of"),
+    };
+    return Ok(Some((option.0.to_string(), option.1)));
+}
+
+/// Binary targets as `(name, path)`, explicit `[[bin]]` tables first then the ones cargo discovers
+fn binary_targets(doc: &TomlDocument, dir: &Path, package_name: &str) -> Vec<(String, String)> {
+    let mut bins: Vec<(String, String)> = Vec::new();
+    let autobins = doc.get("package", "autobins") != Some(&TomlValue::Boolean(false));
+    for bin in doc.array_tables("bin") {
+	let name = match bin.get("name").and_then(|v| v.as_str()) {
+	    Some(name) => name.to_string(),
+	    None => package_name.to_string(),
+	};
+	let path = match bin.get("path").and_then(|v| v.as_str()) {
+	    Some(path) => path.to_string(),
+	    None => default_bin_path(dir, &name, package_name),
+	};
+	bins.push((name, path));
+    }
+    if !autobins {
+	return bins;
+    }
+    let has_path = |bins: &Vec<(String, String)>, path: &str| bins.iter().any(|(_, p)| p == path);
+    if dir.join("src").join("main.rs").is_file() && !has_path(&bins, "src/main.rs") {
+	bins.push((package_name.to_string(), "src/main.rs".to_string()));
+    }
+    if let Ok(entries) = fs::read_dir(dir.join("src").join("bin")) {
+	let mut discovered: Vec<(String, String)> = Vec::new();
+	for entry in entries.flatten() {
+	    let path = entry.path();
+	    let (name, rel) = if path.is_file() && path.extension().map(|e| e == "rs").unwrap_or(false) {
+		let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+		let rel = format!("src/bin/{}.rs", stem);
+		(stem, rel)
+	    } else if path.join("main.rs").is_file() {
+		let stem = entry.file_name().to_string_lossy().to_string();
+		let rel = format!("src/bin/{}/main.rs", stem);
+		(stem, rel)
+	    } else {
+		continue;
+	    };
+	    if !has_path(&bins, &rel) && !bins.iter().any(|(n, _)| *n == name) {
+		discovered.push((name, rel));
+	    }
+	}
+	discovered.sort();
+	bins.extend(discovered);
+    }
+    return bins;
+}
+
+fn default_bin_path(dir: &Path, name: &str, package_name: &str) -> String {
+    if name == package_name && dir.join("src").join("main.rs").is_file() {
+	return "src/main.rs".to_string();
+    }
+    if dir.join("src").join("bin").join(name).join("main.rs").is_file() {
+	return format!("src/bin/{}/main.rs", name);
+    }
+    return format!("src/bin/{}.rs", name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toml_subset;
+
+    fn check(manifest: &str) -> ImportReport {
+	let doc = toml_subset::parse(manifest).unwrap();
+	let mut report = ImportReport { unsupported: Vec::new(), ignored: Vec::new() };
+	check_unsupported_tables(&doc, &mut report);
+	return report;
+    }
+
+    #[test]
+    fn target_dependency_tables_are_unsupported() {
+	let report = check("[target.'cfg(unix)'.dependencies.libc]\nversion = \"0.2\"\n");
+	assert_eq!(report.unsupported, vec!["[target.cfg(unix).dependencies.libc], only the standard library is available"]);
+	let report = check("[target.'cfg(target_os = \"a.b\")'.dependencies]\nlibc = \"0.2\"\n");
+	assert_eq!(report.unsupported.len(), 1);
+    }
+
+    #[test]
+    fn dotted_dependency_keys_are_unsupported() {
+	assert_eq!(check("dependencies.libc = \"0.2\"\n").unsupported.len(), 1);
+	assert_eq!(check("[target.'cfg(unix)']\ndependencies.libc = \"0.2\"\n").unsupported.len(), 1);
+    }
+
+    #[test]
+    fn dev_and_build_dependencies_are_ignored() {
+	let report = check("[dev-dependencies]\nproptest = \"1\"\n[target.'cfg(unix)'.build-dependencies]\ncc = \"1\"\n");
+	assert!(report.unsupported.is_empty());
+	assert_eq!(report.ignored.len(), 2);
+    }
+
+    #[test]
+    fn package_tables_are_fine() {
+	let report = check("[package]\nname = \"x\"\n[package.metadata.dependencies]\na = 1\n[badges]\n");
+	assert!(report.unsupported.is_empty() && report.ignored.is_empty());
+    }
+}
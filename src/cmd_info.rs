@@ -0,0 +1,86 @@
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use kinoko::git_info;
+use kinoko::toolchain;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, ParsedArgs};
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "info";
+
+pub struct InfoCommand;
+
+impl Subcommand for InfoCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .positional("dir", false, "Project folder, defaults to the current one").completes(Completion::Dirs)
+    }
+
+    fn summary(&self) -> &'static str {
+	"Print the mushroom, the rustc it uses and the git commit it would be germinated from"
+    }
+
+    fn help(&self) -> &'static str {
+	"The git commit, branch and dirty flag are read straight from .git, the git binary isn't needed.
+They are the same values germinated programs get as KINOKO_GIT_COMMIT, KINOKO_GIT_BRANCH and KINOKO_GIT_DIRTY.
+Dirty means a tracked file has staged or unstaged changes, untracked files are ignored."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko info", "Describe the current project"),
+	    ("kinoko info path/to/project", "Describe another project"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to describe the project"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let dir = match args.positional("dir") {
+	Some(dir) => ctx.cwd.join(dir),
+	None => ctx.cwd.clone(),
+    };
+    let kin = Kinoko::at(dir).context(ctx.clone()).build();
+    let mushroom = kin.pick_mushroom()?;
+
+    println!("{:<12}{}", "Mushroom:", kin.get_mushroom_path().display());
+    let package = mushroom.get_package_name().unwrap_or_default();
+    match &mushroom.version {
+	Some(version) => println!("{:<12}{} {}", "Package:", package, version),
+	None => println!("{:<12}{}", "Package:", package),
+    }
+    println!("{:<12}{}", "Root:", mushroom.root);
+    println!("{:<12}{}", "Head:", mushroom.head);
+    if let Some(lib) = &mushroom.lib {
+	println!("{:<12}{}", "Lib:", lib);
+    }
+    if !mushroom.profiles.is_empty() {
+	let names: Vec<&str> = mushroom.profiles.iter().map(|(name, _)| name.as_str()).collect();
+	println!("{:<12}{}", "Profiles:", names.join(", "));
+    }
+    match toolchain::resolve_rustc(&kin, &mushroom) {
+	Ok(rustc) => println!("{:<12}{}", "Rustc:", rustc.to_string_lossy()),
+	Err(err) => println!("{:<12}unavailable: {}", "Rustc:", err),
+    }
+    match git_info::read_git_info(&kin.cwd) {
+	Some(git) => {
+	    println!("{:<12}{}", "Commit:", git.commit);
+	    println!("{:<12}{}", "Branch:", git.branch.as_deref().unwrap_or("(detached)"));
+	    println!("{:<12}{}", "Dirty:", if git.dirty { "yes" } else { "no" });
+	},
+	None => println!("{:<12}not a git repository, or no commits yet", "Git:"),
+    }
+    Ok(())
+}
@@ -1,68 +1,391 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::Write;
+use std::process::Command;
+use std::env;
 
-use utility::*;
-use data_structs::*;
+use kinoko::utility::*;
+use kinoko::data_structs::*;
+use kinoko::context::{Context, LogLevel};
+use kinoko::error::KinokoError;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use templates::{Template, DEFAULT_TEMPLATE};
+use user_config::UserConfig;
+use licenses;
+use {search_directory_for_main_function, rust_file_dir_entry_checker};
 
-type CmdResult = Result<(), String>;
+type CmdResult = Result<(), KinokoError>;
 const COMMAND_NAME: &'static str = "init";
 
-pub fn check_args(argv: &Vec<String>) -> bool {
-    if argv.len() < 1 {
-        return false;
-    }
+pub struct InitCommand;
 
-    let cmd_name = COMMAND_NAME.to_string();
-    if argv[0] == cmd_name {
-        return true;
+impl Subcommand for InitCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
     }
 
-    return false;
-}
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::value("template", Some('t'), "name", "Start from a built-in or user template, see `kinoko template list`").complete(Completion::Values("templates")))
+	    .opt(Opt::flag("force", Some('f'), "Overwrite existing files, they're kept otherwise"))
+	    .opt(Opt::value("name", None, "name", "Project name, defaults to the folder's name"))
+	    .opt(Opt::value("path", None, "dir", "Project folder, defaults to the project name").complete(Completion::Dirs))
+	    .opt(Opt::value("vcs", None, "git|none", "Initialize a git repository").complete(Completion::Words(&["git", "none"])))
+	    .opt(Opt::value("license", None, "id", "Write a LICENSE, one of MIT, Apache-2.0, BSD-3-Clause, ISC or Unlicense").complete(Completion::Words(&licenses::LICENSES)))
+	    .opt(Opt::flag("readme", None, "Write a README.md"))
+	    .positional("project-name", false, "Name and folder of the project, defaults to the current folder")
+    }
 
-pub fn usage_message() -> (String, &'static str) {
-    (
-	format!("{} <project-name>", COMMAND_NAME),
+    fn summary(&self) -> &'static str {
 	"Plant a mushroom! Initialize tiny rust project"
-    )
+    }
+
+    fn help(&self) -> &'static str {
+	"Existing sources are adopted as the root instead of being replaced by the template."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko init tool", "Plant a binary project in a new `tool/` folder"),
+	    ("kinoko init -t lib --license MIT --readme", "Turn the current folder into a library project with a LICENSE and README.md"),
+	    ("kinoko init --name \"My Tool\" --path tools/my-tool --vcs git", "Name the project apart from its folder and start a git repository"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to initialize project"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
 }
 
-pub fn run_command(cwd: PathBuf, mut args: Vec<String>) -> CmdResult {
-    // Remove command name
-    args.remove(0);
+/// Single change done to the project folder, all of them are shown before anything is touched
+enum PlanStep {
+    CreateDir(PathBuf),
+    CreateFile(PathBuf, Vec<u8>),
+    OverwriteFile(PathBuf, Vec<u8>),
+    KeepFile(PathBuf, &'static str),
+    MergeGitignore(PathBuf, Vec<String>),
+    InitGit(PathBuf),
+}
 
-    let project_name: String;
-    let project_path: PathBuf;
-    if args.len() < 1 {
-        let maybe_file_name = cwd.file_name();
-        if maybe_file_name.is_none() {
-            return Err("Failed to get current directory name".to_string());
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let template_name = args.value("template").map(String::from);
+    let name_arg = args.value("name").map(String::from);
+    let path_arg = args.value("path");
+    let vcs_arg = args.value("vcs");
+    let license_arg = args.value("license");
+    let force = args.flag("force");
+    let readme = args.flag("readme");
+    let init_git = match vcs_arg {
+        None | Some("none") => false,
+        Some("git") => true,
+        Some(other) => return Err(KinokoError::Args(format!("Unknown version control `{}`, expected `git` or `none`", other))),
+    };
+    if let Some(license) = license_arg {
+        if licenses::license_text(license, "", "").is_none() {
+            return Err(KinokoError::Args(format!("Unknown license `{}`, expected one of: {}", license, licenses::LICENSES.join(", "))));
         }
-        project_name = maybe_file_name.unwrap().to_str().unwrap().to_string();
-        project_path = cwd.clone();
+    }
+    let explicit_template = template_name.is_some();
+    let template = Template::find(ctx, &template_name.unwrap_or(DEFAULT_TEMPLATE.to_string()))?;
+
+    // The positional name is both the display name and the folder, `--name`/`--path` set them apart
+    let positional_name = args.positional("project-name").map(String::from);
+    let project_path = match (path_arg, &positional_name) {
+        (Some(path), _) => ctx.cwd.join(path),
+        (None, Some(name)) => {
+            validate_project_name(name)?;
+            ctx.cwd.join(name)
+        },
+        (None, None) => ctx.cwd.clone(),
+    };
+    let project_name = match name_arg.or(positional_name) {
+        Some(name) => name,
+        None => match project_path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(KinokoError::Init("Failed to get the project's directory name, pass one with --name".to_string())),
+        },
+    };
+    validate_project_name(&project_name)?;
+    let crate_name = derive_crate_name(&project_name)?;
+
+    let mut plan = Vec::new();
+    if !project_path.is_dir() {
+        plan.push(PlanStep::CreateDir(project_path.clone()));
+    }
+    let build_folder_name = "build";
+    let build_path = project_path.join(build_folder_name);
+    if !build_path.is_dir() {
+        plan.push(PlanStep::CreateDir(build_path));
+    }
+
+    // Sources already in the folder are adopted instead of replaced by the template
+    let existing_root = if project_path.is_dir() && !(force && explicit_template) {
+        find_existing_root(ctx, &project_path)
     } else {
-        project_name = args.remove(0);
-        project_path = cwd.join(project_name.clone());
-        if project_path.is_dir() {
-            if !is_dir_empty(&project_path) {
-                return Err("Directory is not empty, can't initialize project on a non-empty directory".to_string());
+        None
+    };
+    let root = match &existing_root {
+        Some(root) => {
+            plan.push(PlanStep::KeepFile(project_path.join(root), "adopted as root"));
+            root.clone()
+        },
+        None => {
+            let vars = [("project_name", project_name.as_str()), ("crate_name", crate_name.as_str())];
+            for file in template.render(&vars).into_iter() {
+                if file.path == Path::new(".gitignore") {
+                    continue;
+                }
+                plan_file(&mut plan, project_path.join(&file.path), file.contents, force);
             }
+            template.root()
+        },
+    };
+
+    let gitignore_path = project_path.join(".gitignore");
+    let gitignore = match template.get_file(".gitignore") {
+        Some(file) => String::from_utf8_lossy(&file.contents).to_string(),
+        None => DEFAULT_GITIGNORE.to_string(),
+    };
+    if gitignore_path.is_file() {
+        let current = match fs::read_to_string(&gitignore_path) {
+            Ok(current) => current,
+            Err(err) => return Err(KinokoError::io_at("read", &gitignore_path, err)),
+        };
+        let missing = missing_gitignore_entries(&current, &gitignore);
+        if missing.is_empty() {
+            plan.push(PlanStep::KeepFile(gitignore_path, "already ignores kinoko's files"));
         } else {
-            create_dir(&project_path)?;
+            plan.push(PlanStep::MergeGitignore(gitignore_path, missing));
         }
+    } else {
+        plan.push(PlanStep::CreateFile(gitignore_path, gitignore.into_bytes()));
     }
 
-    let main_file_name = "main.rs";
-    let source_folder_name = "src";
-    let build_folder_name = "build";
+    let kinoko = Kinoko::at(project_path.clone()).context(ctx.clone()).build();
+    let mushroom_content = match template.get_file("kinoko.🍄") {
+        // Templates saved from a project carry their own mushroom
+        Some(_) if existing_root.is_none() => None,
+        _ => {
+            let mut mushroom = Mushroom::new();
+            mushroom.root = root;
+            mushroom.head = format!("{}/{}", build_folder_name, crate_name);
+            Some(mushroom.serialize())
+        },
+    };
+    if let Some(content) = mushroom_content {
+        plan_file(&mut plan, kinoko.get_mushroom_path(), content.into_bytes(), force);
+    }
 
-    let src_path   = project_path.join(source_folder_name);
-    create_dir(&src_path)?;
-    let build_path = project_path.join(build_folder_name);
-    create_dir(&build_path)?;
-    let gitignore_path = project_path.join(".gitignore");
-    create_file(&gitignore_path, r#"
+    if readme {
+        plan_file(&mut plan, project_path.join("README.md"), readme_stub(&project_name, &crate_name).into_bytes(), force);
+    }
+    if let Some(license) = license_arg {
+        let author = license_author(ctx)?;
+        let year = format!("{}", current_year());
+        let text = licenses::license_text(license, &author, &year).unwrap_or_default();
+        plan_file(&mut plan, project_path.join("LICENSE"), text.into_bytes(), force);
+    }
+    if init_git {
+        if project_path.join(".git").exists() {
+            plan.push(PlanStep::KeepFile(project_path.join(".git"), "already a git repository"));
+        } else {
+            plan.push(PlanStep::InitGit(project_path.clone()));
+        }
+    }
+
+    if ctx.shows(LogLevel::Info) {
+        println!("Planting `{}` at {}", project_name, project_path.display());
+        for step in plan.iter() {
+            print_plan_step(&project_path, step);
+        }
+    }
+    for step in plan.into_iter() {
+        match step {
+            PlanStep::CreateDir(path) => create_dir_all(ctx, &path)?,
+            PlanStep::CreateFile(path, contents) | PlanStep::OverwriteFile(path, contents) => {
+                if let Some(parent) = path.parent() {
+                    if !parent.is_dir() {
+                        create_dir_all(ctx, &parent.to_path_buf())?;
+                    }
+                }
+                create_file(ctx, &path, &contents)?;
+            },
+            PlanStep::KeepFile(_, _) => {},
+            PlanStep::MergeGitignore(path, missing) => append_gitignore(ctx, &path, &missing)?,
+            PlanStep::InitGit(path) => git_init(ctx, &path),
+        }
+    }
+    match existing_root {
+        Some(root) => info!(ctx, "Planted `{}` around existing root {}", project_name, root),
+        None => info!(ctx, "Planted `{}` from template `{}`", project_name, template.name),
+    }
+
+    Ok(())
+}
+
+/// Copyright holder for the license, `author` in the user config or the logged in user
+fn license_author(ctx: &Context) -> Result<String, KinokoError> {
+    if let Some(author) = UserConfig::load(ctx).author {
+        return Ok(author);
+    }
+    let config_hint = match UserConfig::path() {
+        Some(path) => format!("{}", path.display()),
+        None => String::from("the user config"),
+    };
+    return match env::var("USER").or_else(|_| env::var("USERNAME")) {
+        Ok(user) => {
+            warn!(ctx, "No `author` set in {}, using `{}` as the license holder", config_hint, user);
+            Ok(user)
+        },
+        Err(_) => Err(KinokoError::Init(format!("No author for the license, add `author: <name>` to {}", config_hint))),
+    };
+}
+
+fn git_init(ctx: &Context, project_path: &PathBuf) {
+    match Command::new("git").arg("init").arg("--quiet").current_dir(project_path).status() {
+        Ok(status) if status.success() => info!(ctx, "Initialized git repository in {}", project_path.display()),
+        Ok(status) => warn!(ctx, "git init failed with {}, skipping version control", status),
+        Err(err) => warn!(ctx, "Unable to run git, skipping version control: {}", err),
+    }
+}
+
+fn readme_stub(project_name: &str, crate_name: &str) -> String {
+    return format!(r#"# {0}
+
+Tiny rust project built with [kinoko](https://github.com/jmnuf/kinoko), no cargo required.
+
+## Building
+The entry file and output are described in `kinoko.🍄`, to compile run:
+```console
+$ kinoko build
+```
+The program is germinated at `build/{1}`. To build and run it in one go, passing arguments after `--`:
+```console
+$ kinoko build -r -- [args]
+```
+"#, project_name, crate_name);
+}
+
+/// Project names end up as folder names, so they can't point anywhere else
+fn validate_project_name(name: &str) -> CmdResult {
+    if name.trim().is_empty() {
+        return Err(KinokoError::Init("Project name can't be empty".to_string()));
+    }
+    if name == "." || name == ".." || name.contains('/') || name.contains('\\') {
+        return Err(KinokoError::Init(format!("Project name can't contain path separators or point to another directory: {}", name)));
+    }
+    if name.chars().any(|c| c.is_control()) {
+        return Err(KinokoError::Init(format!("Project name can't contain control characters: {:?}", name)));
+    }
+    return Ok(());
+}
+
+/// Turns a project name into a valid rust identifier, dashes and spaces become underscores
+fn derive_crate_name(name: &str) -> Result<String, KinokoError> {
+    let crate_name: String = name.trim().chars().map(|c| if c == '-' || c.is_whitespace() { '_' } else { c }).collect();
+    if let Some(c) = crate_name.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '_')) {
+        return Err(KinokoError::Init(format!("Project name `{}` has a character not allowed in crate names: {:?}", name, c)));
+    }
+    if crate_name.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+        return Err(KinokoError::Init(format!("Project name `{}` can't start with a digit, pass a different one with --name", name)));
+    }
+    if crate_name == "_" || RESERVED_NAMES.contains(&crate_name.as_str()) {
+        return Err(KinokoError::Init(format!("Project name `{}` is reserved by rust, pass a different one with --name", name)));
+    }
+    return Ok(crate_name);
+}
+
+const RESERVED_NAMES: [&'static str; 41] = [
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "std", "core", "test",
+];
+
+fn plan_file(plan: &mut Vec<PlanStep>, path: PathBuf, contents: Vec<u8>, force: bool) {
+    if !path.exists() {
+        plan.push(PlanStep::CreateFile(path, contents));
+    } else if force {
+        plan.push(PlanStep::OverwriteFile(path, contents));
+    } else {
+        plan.push(PlanStep::KeepFile(path, "already exists, pass --force to overwrite"));
+    }
+}
+
+fn print_plan_step(project_path: &Path, step: &PlanStep) {
+    let relative = |path: &PathBuf| -> String {
+        match path.strip_prefix(project_path) {
+            Ok(rel) if rel.as_os_str().is_empty() => String::from("."),
+            Ok(rel) => format!("{}", rel.display()),
+            Err(_) => format!("{}", path.display()),
+        }
+    };
+    match step {
+        PlanStep::CreateDir(path) => println!("  create    {}/", relative(path)),
+        PlanStep::CreateFile(path, _) => println!("  create    {}", relative(path)),
+        PlanStep::OverwriteFile(path, _) => println!("  overwrite {}", relative(path)),
+        PlanStep::KeepFile(path, reason) => println!("  keep      {} ({})", relative(path), reason),
+        PlanStep::MergeGitignore(path, missing) => println!("  merge     {} (+{} entries)", relative(path), missing.len()),
+        PlanStep::InitGit(_) => println!("  run       git init"),
+    }
+}
+
+fn find_existing_root(ctx: &Context, project_path: &PathBuf) -> Option<String> {
+    let main_path = project_path.join("src").join("main.rs");
+    let root_path = if main_path.is_file() {
+        main_path
+    } else {
+        match search_directory_for_main_function(ctx, project_path.clone(), rust_file_dir_entry_checker) {
+            Ok(path) => path,
+            Err(_) => return None,
+        }
+    };
+    let relative = root_path.strip_prefix(project_path).ok()?;
+    let parts: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    return Some(parts.join("/"));
+}
+
+/// Ignore rules from `wanted` that `current` doesn't have yet
+fn missing_gitignore_entries(current: &str, wanted: &str) -> Vec<String> {
+    let existing: Vec<&str> = current.lines().map(|line| line.trim()).collect();
+    let mut missing = Vec::new();
+    for line in wanted.lines() {
+        let line = line.trim();
+        if line.is_empty() || (line.starts_with('#') && !line.starts_with("\\#")) {
+            continue;
+        }
+        if !existing.contains(&line) {
+            missing.push(line.to_string());
+        }
+    }
+    return missing;
+}
+
+fn append_gitignore(ctx: &Context, path: &PathBuf, missing: &Vec<String>) -> CmdResult {
+    let mut file = match fs::OpenOptions::new().append(true).open(path) {
+        Ok(file) => file,
+        Err(error) => return Err(KinokoError::io_at("open", path, error)),
+    };
+    let mut contents = String::from("\n# Added by kinoko\n");
+    for line in missing.iter() {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+    return match file.write_all(contents.as_bytes()) {
+        Err(error) => Err(KinokoError::io_at("write", path, error)),
+        Ok(_) => {
+            verbose!(ctx, "Merged {} entries into {}", missing.len(), path.display());
+            Ok(())
+        },
+    };
+}
+
+const DEFAULT_GITIGNORE: &'static str = r#"
 # Kinoko build output
 build/
 
@@ -82,66 +405,26 @@ Cargo.lock
 # Ignore Emacs stuff
 *~
 \#*\#
-"#.to_string())?;
-    let main_path  = src_path.join(main_file_name);
-    create_file(&main_path, r#"use std::process::ExitCode;
-
-fn run(program: &str, args: Vec<String>) -> Result<(), String> {
-    println!("Hello world!");
-    print!("] {}", program);
-    for arg in args.iter() {
-        print!(" {:?}", arg);
-    }
-    println!();
-    println!("Arguments count: {}", args.len());
-    Ok(())
-}
-
-fn main() -> ExitCode {
-    let mut args:Vec<String> = std::env::args().collect();
-    let program:String = args.remove(0);
-    match run(&program, args) {
-        Ok(_) => ExitCode::SUCCESS,
-        Err(err) => {
-            eprintln!("ERROR: {}", err);
-            ExitCode::FAILURE
-        }
-    }
-}
-"#.to_string())?;
-
-    let mut mushroom = Mushroom::new();
-    mushroom.root = format!("{}/{}", source_folder_name, main_file_name);
-    mushroom.head = format!("{}/{}", build_folder_name, project_name);
-    let mushroom_content = mushroom.serialize();
-    let kinoko = Kinoko::new(project_path.clone());
-    create_file(&kinoko.get_mushroom_path(), mushroom_content)?;
-
-    Ok(())
-}
-
-fn is_dir_empty(path: &PathBuf) -> bool {
-    return path.read_dir().map(|mut x| x.next().is_none()).unwrap_or(false);
-}
+"#;
 
-fn create_dir(path: &PathBuf) -> CmdResult {
-    match fs::create_dir(path) {
-        Err(error) => Err(format!("{}", error)),
+fn create_dir_all(ctx: &Context, path: &PathBuf) -> CmdResult {
+    match fs::create_dir_all(path) {
+        Err(error) => Err(KinokoError::io_at("create directory", path, error)),
         Ok(_) => {
-            info!("Created directory: {}", path.display());
+            verbose!(ctx, "Created directory: {}", path.display());
             Ok(())
         },
     }
 }
 
-fn create_file(path: &PathBuf, contents: String) -> CmdResult {
+fn create_file<C: AsRef<[u8]>>(ctx: &Context, path: &PathBuf, contents: C) -> CmdResult {
     match fs::File::create(path) {
-        Err(error) => Err(format!("{}", error)),
+        Err(error) => Err(KinokoError::io_at("create", path, error)),
         Ok(mut file) => {
-            match file.write_all(&contents.into_bytes()) {
-                Err(error) => Err(format!("{}", error)),
+            match file.write_all(contents.as_ref()) {
+                Err(error) => Err(KinokoError::io_at("write", path, error)),
                 Ok(_) => {
-                    info!("Created file: {}", path.display());
+                    verbose!(ctx, "Created file: {}", path.display());
 
                     Ok(())
                 },
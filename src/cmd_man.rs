@@ -0,0 +1,247 @@
+use std::fs;
+use std::path::PathBuf;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use kinoko::utility::{info, verbose};
+use subcommand::{Subcommand, Registry, command_spec, global_options};
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use commands;
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "man";
+/// Page documenting the kinoko.🍄 format, man pages can't be looked up by emoji comfortably
+const MUSHROOM_PAGE: &'static str = "kinoko-mushroom";
+
+/// Keys of the kinoko.🍄 and what they do
+const MUSHROOM_KEYS: [(&'static str, &'static str); 15] = [
+    ("root", "Entry file of the program, where the main function is. Required."),
+    ("head", "Output file of the program. Defaults to build/app when missing."),
+    ("history", "How many germinated heads are kept in the .history/ folder next to the head."),
+    ("edition", "Rust edition passed to rustc as --edition, rustc uses 2015 without it."),
+    ("features", "Space separated features always enabled as --cfg feature=\"<name>\"."),
+    ("profile.<name>", "Extra rustc flags only added with kinoko build --profile <name>."),
+    ("lib", "Root of a library compiled next to the head as lib<name>.rlib and passed to it with --extern."),
+    ("lib-name", "Crate name of the library, defaults to the head's name with dashes as underscores."),
+    ("rustc", "Compiler to germinate with, a path relative to the project or a name looked up in the PATH. RUSTC and kinoko +<toolchain> take precedence over it."),
+    ("rust-version", "Oldest rustc the project germinates with, like 1.70. Older compilers fail before compiling."),
+    ("name, version, authors, description", "Package details set as KINOKO_PKG_NAME, KINOKO_PKG_VERSION, KINOKO_PKG_AUTHORS and KINOKO_PKG_DESCRIPTION while rustc runs, for env!. The name defaults to the head's name and authors go one per line, repeating the key."),
+    ("cargo-env", "true also sets the package details as CARGO_PKG_*, for code written for cargo."),
+    ("build-script", "Build script compiled and ran before rustc, a build.rs next to kinoko.🍄 is used without it. Its kinoko:rustc-cfg=, kinoko:rustc-env= and kinoko:rerun-if-changed= lines on stdout are read and OUT_DIR points to a folder next to the head."),
+    ("pre-build", "Shell command ran from the project folder before rustc, one per line. A failure stops the germination."),
+    ("post-build", "Shell command ran from the project folder after a successful germination, one per line. A failure is reported and the new head kept. Hooks get KINOKO_PROJECT_DIR, KINOKO_ROOT, KINOKO_HEAD, KINOKO_LIB, KINOKO_PROFILE, KINOKO_TARGET and KINOKO_RUSTC."),
+];
+
+/// Exit codes of kinoko and what they mean
+const EXIT_STATUS: [(&'static str, &'static str); 10] = [
+    ("0", "Success."),
+    ("1", "Any other failure."),
+    ("2", "Bad usage: unknown command, unknown option or bad option value."),
+    ("3", "No kinoko.🍄 found."),
+    ("4", "The kinoko.🍄 can't be read, its root doesn't exist or the profile or target is unknown."),
+    ("5", "rustc reported compile errors."),
+    ("6", "rustc couldn't be started, usually because it's not in the PATH, or is older than the rust-version of kinoko.🍄."),
+    ("7", "init can't plant the project as asked."),
+    ("8", "Reading or writing files failed."),
+    ("9", "A pre-build or post-build hook of kinoko.🍄 failed."),
+];
+
+pub struct ManCommand;
+
+impl Subcommand for ManCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::value("out", Some('o'), "dir", "Write every page into `<dir>/man1` and `<dir>/man5` instead").complete(Completion::Dirs))
+	    .positional("page", false, "Page or command to print the page of, defaults to `kinoko`").completes(Completion::Values("pages"))
+    }
+
+    fn summary(&self) -> &'static str {
+	"Print or install the man pages of kinoko, its commands and the kinoko.🍄 format"
+    }
+
+    fn help(&self) -> &'static str {
+	"Pages are `kinoko`, `kinoko-<command>` for each command and `kinoko-mushroom` for the kinoko.🍄 format."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko man build | man -l -", "Read the page of `kinoko build` without installing it"),
+	    ("sudo kinoko man -o /usr/local/share/man", "Install every page so `man kinoko` works"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to generate man pages"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let registry = commands();
+    if let Some(out) = args.value("out") {
+	if args.positional("page").is_some() {
+	    return Err(KinokoError::Args(String::from("`--out` writes every page, a page can't be picked with it")));
+	}
+	let out = ctx.cwd.join(out);
+	for (name, section) in page_names(&registry) {
+	    let dir = out.join(format!("man{}", section));
+	    if let Err(e) = fs::create_dir_all(&dir) {
+		return Err(KinokoError::io(format!("Failed to create {}", dir.display()), e));
+	    }
+	    let path: PathBuf = dir.join(format!("{}.{}", name, section));
+	    if let Err(e) = fs::write(&path, render_page(&registry, &name).unwrap_or_default()) {
+		return Err(KinokoError::io(format!("Failed to write {}", path.display()), e));
+	    }
+	    verbose!(ctx, "Wrote {}", path.display());
+	}
+	info!(ctx, "Man pages written to {}", out.display());
+	return Ok(());
+    }
+    let name = args.positional("page").unwrap_or("kinoko");
+    return match render_page(&registry, name) {
+	Some(page) => {
+	    print!("{}", page);
+	    Ok(())
+	},
+	None => Err(KinokoError::Args(format!("No man page named `{}`, expected one of: {}", name, page_names(&registry).into_iter().map(|(name, _)| name).collect::<Vec<String>>().join(", ")))),
+    };
+}
+
+/// Every page as `(name, section)`
+pub fn page_names(registry: &Registry) -> Vec<(String, u8)> {
+    let mut pages = vec![(String::from("kinoko"), 1)];
+    pages.extend(registry.iter().map(|cmd| (format!("kinoko-{}", cmd.name()), 1)));
+    pages.push((String::from(MUSHROOM_PAGE), 5));
+    return pages;
+}
+
+fn render_page(registry: &Registry, name: &str) -> Option<String> {
+    if name == "kinoko" {
+	return Some(kinoko_page(registry));
+    }
+    if name == MUSHROOM_PAGE {
+	return Some(mushroom_page());
+    }
+    // `kinoko man build` reads as well as `kinoko man kinoko-build`
+    let cmd = registry.find(name.strip_prefix("kinoko-").unwrap_or(name))?;
+    return Some(command_page(cmd));
+}
+
+/// Escapes text so roff prints it as is
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+	return format!("\\&{}", text);
+    }
+    return text;
+}
+
+/// `.TP` paragraph with a bold tag
+fn tagged(tag: &str, text: &str) -> String {
+    return format!(".TP\n\\fB{}\\fR\n{}\n", roff(tag), roff(text));
+}
+
+/// Lines of free text, each one its own paragraph
+fn paragraphs(text: &str) -> String {
+    return text.lines().map(|line| format!("{}\n", roff(line))).collect::<Vec<String>>().join(".PP\n");
+}
+
+fn header(name: &str, section: u8, description: &str) -> String {
+    return format!(".TH {} {} \"\" \"kinoko\" \"Kinoko Manual\"\n.SH NAME\n{} \\- {}\n", name.to_uppercase(), section, roff(name), roff(description));
+}
+
+fn options_section(options: &Vec<Opt>) -> String {
+    let mut section = String::from(".SH OPTIONS\n");
+    for opt in options.iter() {
+	section.push_str(&tagged(&opt.signature(), opt.help));
+    }
+    return section;
+}
+
+fn kinoko_page(registry: &Registry) -> String {
+    let mut page = header("kinoko", 1, "compile small rust projects with rustc");
+    page.push_str(".SH SYNOPSIS\n\\fBkinoko\\fR [\\fI+toolchain\\fR] [\\fIoptions\\fR] \\fIcommand\\fR [\\fIargs\\fR]\n");
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&paragraphs("Kinoko runs rustc for projects that only use the standard library. The entry file and output of a project are read from the kinoko.🍄 in its folder, see kinoko-mushroom(5)."));
+    page.push_str(".PP\n");
+    page.push_str(&paragraphs("With +<toolchain> first, rustc is the one rustup has for that toolchain. Otherwise it's RUSTC, the rustc key of kinoko.🍄, the toolchain of a rust-toolchain or rust-toolchain.toml file in the project, or the rustc in the PATH, in that order."));
+    page.push_str(".SH COMMANDS\n");
+    for cmd in registry.iter() {
+	page.push_str(&tagged(cmd.name(), &format!("{} See kinoko-{}(1).", cmd.summary(), cmd.name())));
+    }
+    page.push_str(&tagged("help [command]", "List the commands, or show the usage of one."));
+    page.push_str(&options_section(&global_options()));
+    page.push_str(".SH PLUGINS\n");
+    page.push_str(&paragraphs("When a command isn't built in, an executable named kinoko-<command> on the PATH is ran with the rest of the arguments. It gets KINOKO, KINOKO_PROJECT_DIR, KINOKO_MUSHROOM and KINOKO_HEAD in its environment and its exit code becomes kinoko's."));
+    page.push_str(".SH ENVIRONMENT\n");
+    page.push_str(&tagged("NO_COLOR", "Disables colors unless --color always is passed."));
+    page.push_str(&tagged("RUSTC", "Compiler to germinate with, over the rustc key of kinoko.🍄 and the project's rust-toolchain file."));
+    page.push_str(&tagged("KINOKO_GIT_COMMIT, KINOKO_GIT_SHORT_COMMIT, KINOKO_GIT_BRANCH, KINOKO_GIT_DIRTY", "Set while rustc runs when the project is in a git repository with a commit, read from .git without the git binary. The branch is empty on a detached HEAD. Dirty is true when a tracked file has staged or unstaged changes, untracked files are ignored, and anything that can't be read counts as dirty."));
+    page.push_str(".SH EXIT STATUS\n");
+    for (code, meaning) in EXIT_STATUS.iter() {
+	page.push_str(&tagged(code, meaning));
+    }
+    page.push_str(".SH SEE ALSO\n");
+    let mut see_also: Vec<String> = registry.iter().map(|cmd| format!("kinoko-{}(1)", cmd.name())).collect();
+    see_also.push(format!("{}(5)", MUSHROOM_PAGE));
+    see_also.push(String::from("rustc(1)"));
+    page.push_str(&format!("{}\n", roff(&see_also.join(", "))));
+    return page;
+}
+
+fn command_page(cmd: &dyn Subcommand) -> String {
+    let name = format!("kinoko-{}", cmd.name());
+    let spec = command_spec(cmd);
+    let mut page = header(&name, 1, cmd.summary());
+    page.push_str(&format!(".SH SYNOPSIS\n\\fBkinoko {}\\fR {}\n", roff(cmd.name()), roff(&cmd.args().usage())));
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&paragraphs(cmd.summary()));
+    if !cmd.help().is_empty() {
+	page.push_str(".PP\n");
+	page.push_str(&paragraphs(cmd.help()));
+    }
+    if !cmd.aliases().is_empty() {
+	page.push_str(".PP\n");
+	page.push_str(&paragraphs(&format!("Also ran as: {}", cmd.aliases().join(", "))));
+    }
+    if !spec.positionals.is_empty() || spec.trailing.is_some() {
+	page.push_str(".SH ARGUMENTS\n");
+	for positional in spec.positionals.iter() {
+	    page.push_str(&tagged(positional.name, positional.help));
+	}
+	if let Some((trailing, help)) = spec.trailing {
+	    page.push_str(&tagged(&format!("-- <{}>...", trailing), help));
+	}
+    }
+    page.push_str(&options_section(&spec.options));
+    if !cmd.examples().is_empty() {
+	page.push_str(".SH EXAMPLES\n");
+	for (example, description) in cmd.examples().iter() {
+	    page.push_str(&tagged(example, description));
+	}
+    }
+    page.push_str(".SH SEE ALSO\nkinoko(1)\n");
+    return page;
+}
+
+fn mushroom_page() -> String {
+    let mut page = header(MUSHROOM_PAGE, 5, "the kinoko.🍄 file of a kinoko project");
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&paragraphs("The kinoko.🍄 file sits at the top of a project and tells kinoko how to call rustc.
+Each line is a key and its value separated by a colon, lines without a colon and unknown keys are ignored."));
+    page.push_str(".SH KEYS\n");
+    for (key, meaning) in MUSHROOM_KEYS.iter() {
+	page.push_str(&tagged(key, meaning));
+    }
+    page.push_str(".SH EXAMPLE\n.nf\n");
+    page.push_str(&roff("root: src/main.rs\nhead: build/tool\nedition: 2021\nfeatures: fancy logging\nprofile.release: -C opt-level=3 -C strip=symbols\n"));
+    page.push_str(".fi\n");
+    page.push_str(".SH SEE ALSO\nkinoko(1), kinoko\\-build(1)\n");
+    return page;
+}
@@ -0,0 +1,69 @@
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use subcommand::Subcommand;
+use args::{ArgSpec, ParsedArgs};
+use kinoko::head_history;
+use kinoko::utility::format_unix_timestamp;
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "rollback";
+
+pub struct RollbackCommand;
+
+impl Subcommand for RollbackCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .positional("n", false, "How many builds to go back, defaults to 1")
+    }
+
+    fn summary(&self) -> &'static str {
+	"Restore the head germinated `n` builds ago (defaults to 1)"
+    }
+
+    fn help(&self) -> &'static str {
+	"See `history` for the available heads, `rollback 0` puts back the newest one."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko rollback", "Put back the head germinated before the current one"),
+	    ("kinoko rollback 2", "Put back the head germinated two builds ago"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Failed to rollback"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let steps_back = args.positional_as::<usize>("n")?.unwrap_or(1);
+
+    let kin = Kinoko::at(ctx.cwd.clone()).context(ctx.clone()).build();
+    let mushroom = kin.pick_mushroom()?;
+    let heads = match head_history::list_heads(&mushroom, &kin) {
+	Ok(heads) => heads,
+	Err(err) => return Err(KinokoError::io("Failed to read head history", err)),
+    };
+    let head = match heads.get(steps_back) {
+	Some(head) => head,
+	None => return Err(KinokoError::Other(format!("History only holds {} head(s), can't go back {} build(s)", heads.len(), steps_back))),
+    };
+
+    return match head_history::restore_head(head, &mushroom, &kin) {
+	Ok(_) => {
+	    println!("Rolled back to head germinated at {} ({})", format_unix_timestamp(head.timestamp), head.fingerprint);
+	    Ok(())
+	},
+	Err(err) => Err(KinokoError::io("Failed to restore head", err)),
+    };
+}
@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+
+use kinoko::data_structs::*;
+use kinoko::context::Context;
+use kinoko::error::KinokoError;
+use subcommand::Subcommand;
+use args::{ArgSpec, Completion, Opt, ParsedArgs};
+use templates::{self, Template};
+use kinoko::utility::info;
+
+type CmdResult = Result<(), KinokoError>;
+const COMMAND_NAME: &'static str = "template";
+
+pub struct TemplateCommand;
+
+impl Subcommand for TemplateCommand {
+    fn name(&self) -> &'static str {
+	COMMAND_NAME
+    }
+
+    fn args(&self) -> ArgSpec {
+	ArgSpec::new()
+	    .opt(Opt::flag("force", Some('f'), "Replace a saved template with the same name"))
+	    .positional("action", true, "`list` the templates or `save` the current project").completes(Completion::Words(&["list", "save"]))
+	    .positional("name", false, "Name to save the template as")
+    }
+
+    fn summary(&self) -> &'static str {
+	"List the templates for `init` or save the current project as one"
+    }
+
+    fn help(&self) -> &'static str {
+	"Saved templates go to the `templates/` folder of the user config and replace the project name with {{project_name}}."
+    }
+
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+	&[
+	    ("kinoko template list", "List the built-in and saved templates"),
+	    ("kinoko template save tiny-cli", "Save the current project as the `tiny-cli` template"),
+	]
+    }
+
+    fn failure_message(&self) -> &'static str {
+	"Template command failed"
+    }
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult {
+	run_command(ctx, args)
+    }
+}
+
+fn run_command(ctx: &Context, args: ParsedArgs) -> CmdResult {
+    let action = args.positional("action").unwrap_or_default();
+    return match (action, args.positional("name")) {
+	("list", None) => {
+	    for (name, desc) in templates::list_templates().iter() {
+		println!(" {:<20}{}", name, desc);
+	    }
+	    Ok(())
+	},
+	("list", Some(name)) => Err(KinokoError::Args(format!("Unexpected argument: {}", name))),
+	("save", Some(name)) => save_template(ctx, name, args.flag("force")),
+	("save", None) => Err(KinokoError::Args("Missing name for the template".to_string())),
+	_ => Err(KinokoError::Args(format!("Unknown template subcommand: {}", action))),
+    };
+}
+
+fn save_template(ctx: &Context, name: &str, force: bool) -> CmdResult {
+    if !templates::is_valid_name(name) {
+	return Err(KinokoError::Args(format!("Invalid template name: {}", name)));
+    }
+    let kin = Kinoko::at(ctx.cwd.clone()).context(ctx.clone()).build();
+    let mushroom = kin.pick_mushroom()?;
+    let project_name = match Path::new(&mushroom.head).file_name() {
+	Some(name) => name.to_string_lossy().to_string(),
+	None => return Err(KinokoError::Other(format!("Mushroom head has no name: {}", mushroom.head))),
+    };
+    let template_dir = match templates::user_templates_dir() {
+	Some(dir) => dir.join(name),
+	None => return Err(KinokoError::Other("Unable to find the user's config folder, is HOME set?".to_string())),
+    };
+    if template_dir.exists() {
+	if !force {
+	    return Err(KinokoError::Other(format!("Template already exists, pass --force to replace it: {}", template_dir.display())));
+	}
+	if let Err(err) = fs::remove_dir_all(&template_dir) {
+	    return Err(KinokoError::io_at("remove old template", &template_dir, err));
+	}
+    }
+
+    // Germinated output is not part of the template
+    let mut skipped = vec![PathBuf::from(".git"), PathBuf::from("target")];
+    if let Some(head_dir) = Path::new(&mushroom.head).components().next() {
+	if Path::new(&mushroom.head).components().count() > 1 {
+	    skipped.push(PathBuf::from(head_dir.as_os_str()));
+	}
+    }
+    let project = Template::load(ctx, name, &ctx.cwd, &skipped)?;
+    let mut saved = 0;
+    for file in project.files.iter() {
+	let relative = replace_word(&file.path.to_string_lossy(), &project_name, "{{project_name}}");
+	let target = template_dir.join(relative);
+	let contents = match String::from_utf8(file.contents.clone()) {
+	    Ok(text) => replace_word(&text, &project_name, "{{project_name}}").into_bytes(),
+	    Err(_) => file.contents.clone(),
+	};
+	if let Some(parent) = target.parent() {
+	    if let Err(err) = fs::create_dir_all(parent) {
+		return Err(KinokoError::io_at("create", &parent.to_path_buf(), err));
+	    }
+	}
+	if let Err(err) = fs::write(&target, contents) {
+	    return Err(KinokoError::io_at("write", &target, err));
+	}
+	saved += 1;
+    }
+    info!(ctx, "Saved {} file(s) as template `{}` at {}", saved, name, template_dir.display());
+    Ok(())
+}
+
+/// Replaces `word` only where it isn't part of a longer identifier
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    if word.is_empty() {
+	return text.to_string();
+    }
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find(word) {
+	let before = if idx > 0 { rest[..idx].chars().last() } else { result.chars().last() };
+	let after = rest[idx + word.len()..].chars().next();
+	result.push_str(&rest[..idx]);
+	if before.map(|c| is_ident(c)).unwrap_or(false) || after.map(|c| is_ident(c)).unwrap_or(false) {
+	    result.push_str(word);
+	} else {
+	    result.push_str(replacement);
+	}
+	rest = &rest[idx + word.len()..];
+    }
+    result.push_str(rest);
+    return result;
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+/// How much kinoko talks while working
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// `-q`, only errors
+    Quiet,
+    /// Errors, warnings and the outcome of each step
+    Normal,
+    /// `-v`, also the details of what is being worked on
+    Verbose,
+    /// `-vv`, also the exact commands being ran
+    Debug,
+}
+
+/// Level of a message printed through the `error!`, `warn!`, `info!`, `verbose!` and `debug!` macros
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Verbose,
+    Debug,
+}
+impl LogLevel {
+    /// Least verbosity at which messages of this level are shown
+    pub fn min_verbosity(&self) -> Verbosity {
+        return match self {
+            LogLevel::Error => Verbosity::Quiet,
+            LogLevel::Warn | LogLevel::Info => Verbosity::Normal,
+            LogLevel::Verbose => Verbosity::Verbose,
+            LogLevel::Debug => Verbosity::Debug,
+        };
+    }
+
+    pub fn label(&self) -> &'static str {
+        return match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Verbose => "VERBOSE",
+            LogLevel::Debug => "DEBUG",
+        };
+    }
+
+    /// ANSI color code of the label
+    pub fn color(&self) -> &'static str {
+        return match self {
+            LogLevel::Error => "31",
+            LogLevel::Warn => "33",
+            LogLevel::Info => "32",
+            LogLevel::Verbose => "36",
+            LogLevel::Debug => "35",
+        };
+    }
+
+    /// Errors and warnings go to stderr, everything else to stdout
+    pub fn to_stderr(&self) -> bool {
+        return match self {
+            LogLevel::Error | LogLevel::Warn => true,
+            _ => false,
+        };
+    }
+}
+
+/// Whether kinoko's own messages are colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color only when writing to a terminal and `NO_COLOR` isn't set
+    Auto,
+    Always,
+    Never,
+}
+impl ColorChoice {
+    /// Parses the value of `--color`
+    pub fn parse(value: &str) -> Option<ColorChoice> {
+        return match value {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        };
+    }
+}
+
+/// Everything a run of kinoko depends on besides its arguments.
+/// Created once by the caller and handed down, so several can live in the same process.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Folder relative paths are resolved against, in place of the process' current directory
+    pub cwd: PathBuf,
+    pub verbosity: Verbosity,
+    pub color: ColorChoice,
+    /// Toolchain picked with `kinoko +<toolchain>`, its rustc is found through rustup
+    pub toolchain: Option<String>,
+}
+
+impl Context {
+    pub fn new<P: Into<PathBuf>>(cwd: P) -> Context {
+        return Context {
+            cwd: cwd.into(),
+            verbosity: Verbosity::Normal,
+            color: ColorChoice::Auto,
+            toolchain: None,
+        };
+    }
+
+    /// Context for the process' current directory
+    pub fn from_current_dir() -> io::Result<Context> {
+        return Ok(Context::new(env::current_dir()?));
+    }
+
+    pub fn shows(&self, level: LogLevel) -> bool {
+        return self.verbosity >= level.min_verbosity();
+    }
+
+    /// Whether messages written to stderr (or stdout) should be colored
+    pub fn use_color(&self, stderr: bool) -> bool {
+        return match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                // https://no-color.org: any non empty value disables color
+                if env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty()) {
+                    return false;
+                }
+                if stderr { io::stderr().is_terminal() } else { io::stdout().is_terminal() }
+            },
+        };
+    }
+
+    /// Prints a message if the verbosity allows it, prefer the logging macros over calling this
+    pub fn log(&self, level: LogLevel, message: String) {
+        if !self.shows(level) {
+            return;
+        }
+        let stderr = level.to_stderr();
+        let label = if self.use_color(stderr) {
+            format!("\x1b[{}m[{}]\x1b[0m", level.color(), level.label())
+        } else {
+            format!("[{}]", level.label())
+        };
+        if stderr {
+            eprintln!("{} {}", label, message);
+        } else {
+            println!("{} {}", label, message);
+        }
+    }
+}
@@ -1,14 +1,27 @@
-use std::env; 
 use std::process::Command;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::ffi::OsStr;
 
-use utility::{info, error, path_move};
+use utility::{info, warn, error, verbose, debug, path_move, command_line};
+use context::Context;
+use head_history;
+use toolchain;
+use hooks::{self, HookStage};
+use build_script::{self, BuildScriptOutput};
+use git_info::{self, GitInfo};
 
 pub struct Kinoko {
     pub argv: Vec<String>,
     pub argc: usize,
     pub cwd: PathBuf,
+    pub ctx: Context,
+    /// Name of the mushroom profile whose rustc flags are added to the germination
+    pub profile: Option<String>,
+    /// Prints each rustc command line before running it
+    pub print_commands: bool,
+    /// Germinates only this target, the head's or the library's name
+    pub only: Option<String>,
 }
 
 #[derive(Debug)]
@@ -18,6 +31,26 @@ pub enum GerminationError {
     InvalidRoot(String),
     NoHeadDir(std::io::Error),
     GrowthFailure(String),
+    UnknownProfile(String),
+    UnknownTarget(String),
+    /// `--only` was given a name the head and library share
+    AmbiguousTarget(String),
+    /// rustc couldn't be started at all, usually because it's not in the PATH
+    RustcUnavailable(std::io::Error),
+    /// The rustc that was picked and where it was picked from
+    RustcMissing(String, String),
+    /// The new head was compiled but couldn't replace the current one
+    HeadUnplaceable(PathBuf),
+    /// The toolchain asked for with `+<toolchain>` and why its rustc can't be found
+    ToolchainUnavailable(String, String),
+    /// The mushroom's `rust-version` and the older version of the rustc found
+    RustcTooOld(String, String),
+    /// A `pre-build` or `post-build` command and how it failed
+    HookFailed(HookStage, String, String),
+    /// How the build script failed to compile or run
+    BuildScriptFailed(String),
+    /// The mushroom's `build-script:` isn't a file
+    MissingBuildScript(PathBuf),
 }
 impl std::fmt::Display for GerminationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -37,28 +70,138 @@ impl std::fmt::Display for GerminationError {
 	    GerminationError::GrowthFailure(failure) => {
 		write!(f, "Germination has failed to be done - {}", failure)
 	    },
+	    GerminationError::UnknownProfile(profile) => {
+		write!(f, "Mushroom has no profile named `{}`, add a `profile.{}:` line to it", profile, profile)
+	    },
+	    GerminationError::UnknownTarget(target) => {
+		write!(f, "Mushroom has no target named `{}`, only its head and library can be germinated", target)
+	    },
+	    GerminationError::AmbiguousTarget(target) => {
+		write!(f, "Both the head and the library are named `{}`, set `lib-name:` in the mushroom to germinate only one of them", target)
+	    },
+	    GerminationError::RustcUnavailable(io_error) => {
+		if io_error.kind() == std::io::ErrorKind::NotFound {
+		    write!(f, "rustc was not found, is it installed and in your PATH? {}", io_error)
+		} else {
+		    write!(f, "Failed to run rustc: {}", io_error)
+		}
+	    },
+	    GerminationError::RustcMissing(rustc, source) => {
+		write!(f, "rustc `{}` from {} was not found, is it installed?", rustc, source)
+	    },
+	    GerminationError::HeadUnplaceable(path) => {
+		write!(f, "Germinated head couldn't be put in place: {}", path.display())
+	    },
+	    GerminationError::ToolchainUnavailable(toolchain, reason) => {
+		write!(f, "Toolchain `+{}` can't be used: {}", toolchain, reason)
+	    },
+	    GerminationError::RustcTooOld(required, found) => {
+		write!(f, "Mushroom needs rustc {} or newer but found rustc {}, update rust or pick a newer toolchain", required, found)
+	    },
+	    GerminationError::BuildScriptFailed(failure) => {
+		write!(f, "Build script failed, {}: No germination done", failure)
+	    },
+	    GerminationError::MissingBuildScript(path) => {
+		write!(f, "Build script named by the mushroom doesn't exist: {}", path.display())
+	    },
+	    GerminationError::HookFailed(stage, hook, failure) => match stage {
+		HookStage::PreBuild => write!(f, "{} hook `{}` {}, no germination done", stage.key(), hook, failure),
+		HookStage::PostBuild => write!(f, "{} hook `{}` {}, the new head was kept", stage.key(), hook, failure),
+	    },
 	}
     }
 }
-impl std::error::Error for GerminationError {}
+impl std::error::Error for GerminationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+	match self {
+	    GerminationError::NoHeadDir(io_error) => Some(io_error),
+	    GerminationError::RustcUnavailable(io_error) => Some(io_error),
+	    _ => None,
+	}
+    }
+}
+
+/// Sets up a `Kinoko` for a project folder, see `Kinoko::at`
+pub struct KinokoBuilder {
+    cwd: PathBuf,
+    ctx: Option<Context>,
+    profile: Option<String>,
+    extra_args: Vec<String>,
+    print_commands: bool,
+    only: Option<String>,
+}
+impl KinokoBuilder {
+    /// Context the project folder is resolved against, without one the folder is used as given
+    pub fn context(mut self, ctx: Context) -> Self {
+        self.ctx = Some(ctx);
+        return self;
+    }
+
+    /// Mushroom profile whose flags are added to rustc
+    pub fn profile<S: Into<String>>(mut self, profile: S) -> Self {
+        self.profile = Some(profile.into());
+        return self;
+    }
+
+    /// Arguments passed as they are to rustc after the mushroom's own
+    pub fn extra_args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.extra_args.extend(args.into_iter().map(|arg| arg.into()));
+        return self;
+    }
+
+    /// Prints the rustc command lines as they're ran
+    pub fn print_commands(mut self, print_commands: bool) -> Self {
+        self.print_commands = print_commands;
+        return self;
+    }
+
+    /// Germinates a single target, the library's name skips the head
+    pub fn only<S: Into<String>>(mut self, target: S) -> Self {
+        self.only = Some(target.into());
+        return self;
+    }
+
+    pub fn build(self) -> Kinoko {
+        let mut kinoko = Kinoko::new_with_args(self.cwd, self.extra_args);
+        if let Some(ctx) = self.ctx {
+            kinoko.cwd = ctx.cwd.join(&kinoko.cwd);
+            kinoko.ctx = ctx;
+        }
+        kinoko.profile = self.profile;
+        kinoko.print_commands = self.print_commands;
+        kinoko.only = self.only;
+        return kinoko;
+    }
+
+    pub fn germinate(self) -> Result<PathBuf, GerminationError> {
+        return self.build().try_germinate();
+    }
+}
 
 impl Kinoko {
-    pub fn new(cwd: PathBuf) -> Self {
-        let argv:Vec<String> = env::args().collect();
-        let argc:usize = argv.len();
-        return Kinoko {
-            argv: argv,
-            argc: argc,
-            cwd: cwd,
+    /// Starts building a `Kinoko` for the project at `cwd`, where the kinoko.🍄 is expected
+    pub fn at<P: Into<PathBuf>>(cwd: P) -> KinokoBuilder {
+        return KinokoBuilder {
+            cwd: cwd.into(),
+            ctx: None,
+            profile: None,
+            extra_args: Vec::new(),
+            print_commands: false,
+            only: None,
         };
     }
 
+    /// A `Kinoko` whose context is the project folder itself, the process' current directory is never read
     pub fn new_with_args(cwd: PathBuf, args: Vec<String>) -> Self {
 	let argc = args.len();
 	return Kinoko {
 	    argv: args,
 	    argc: argc,
+	    ctx: Context::new(cwd.clone()),
 	    cwd: cwd,
+	    profile: None,
+	    print_commands: false,
+	    only: None,
 	};
     }
 
@@ -72,9 +215,7 @@ impl Kinoko {
     }
 
     pub fn get_mushroom_head_path(&self, mushroom: &Mushroom) -> PathBuf {
-        let mut m_head = mushroom.head.clone();
-        #[cfg(target_family="windows")]
-        { m_head.push_str(".exe"); }
+        let m_head = if cfg!(target_family="windows") { format!("{}.exe", mushroom.head) } else { mushroom.head.clone() };
         let path = self.cwd.join(&m_head);
         return path;
     }
@@ -88,27 +229,135 @@ impl Kinoko {
         return path;
     }
 
+    /// Path rustc writes to, it only replaces the head after a successful germination
+    pub fn get_mushroom_new_head_path(&self, mushroom: &Mushroom) -> PathBuf {
+        let head_path = self.cwd.join(&mushroom.head);
+        let mut new_name = std::ffi::OsString::from(".");
+        new_name.push(head_path.file_name().unwrap_or_default());
+        new_name.push(".germinating");
+        #[cfg(target_family="windows")]
+        { new_name.push(".exe"); }
+        return head_path.with_file_name(new_name);
+    }
+
+    /// Where the mushroom's library is compiled to, next to the head
+    pub fn get_mushroom_lib_path(&self, mushroom: &Mushroom) -> Option<PathBuf> {
+        let lib_name = mushroom.get_lib_name()?;
+        let head_path = self.cwd.join(&mushroom.head);
+        return Some(head_path.with_file_name(format!("lib{}.rlib", lib_name)));
+    }
+
+    pub fn get_mushroom_history_dir(&self, mushroom: &Mushroom) -> PathBuf {
+        let head_path = self.cwd.join(&mushroom.head);
+        let head_dir = match head_path.parent() {
+            Some(dir) => dir.to_path_buf(),
+            None => self.cwd.clone(),
+        };
+        return head_dir.join(".history");
+    }
+
     pub fn mushroom_head_exists(&self, mushroom: &Mushroom) -> bool {
         let head_path = self.get_mushroom_head_path(mushroom);
         return head_path.is_file();
     }
 
-    pub fn try_germinate(&self) -> Result<PathBuf, GerminationError> {
+    pub fn pick_mushroom(&self) -> Result<Mushroom, GerminationError> {
         if ! self.has_roots_at_cwd() {
             return Err(GerminationError::MissingRoots(self.cwd.clone()));
         }
         let mushroom_path = self.get_mushroom_path();
-        let mushroom = Mushroom::deserialize(&mushroom_path);
-        let mushroom = match mushroom {
-            Some(v) => v,
-            None => return Err(GerminationError::MushroomUnpickable(mushroom_path)),
+        return match Mushroom::deserialize(&self.ctx, &mushroom_path) {
+            Some(v) => Ok(v),
+            None => Err(GerminationError::MushroomUnpickable(mushroom_path)),
         };
-        info!("Mushroom.root = {}", mushroom.root);
-        info!("Mushroom.head = {}", mushroom.head);
+    }
+
+    /// Picks the mushroom and checks that its roots and the requested profile exist
+    fn pick_healthy_mushroom(&self) -> Result<Mushroom, GerminationError> {
+        let mushroom = self.pick_mushroom()?;
+        verbose!(&self.ctx, "Mushroom.root = {}", mushroom.root);
+        verbose!(&self.ctx, "Mushroom.head = {}", mushroom.head);
+        if let Some(profile) = &self.profile {
+            if mushroom.get_profile(profile).is_none() {
+                return Err(GerminationError::UnknownProfile(profile.clone()));
+            }
+            verbose!(&self.ctx, "Mushroom.profile = {}", profile);
+        }
         let source_path = self.cwd.join(&mushroom.root);
         if ! source_path.is_file() {
 	   return Err(GerminationError::InvalidRoot(mushroom.root));
         }
+        if let Some(lib) = &mushroom.lib {
+            if ! self.cwd.join(lib).is_file() {
+                return Err(GerminationError::InvalidRoot(lib.clone()));
+            }
+            verbose!(&self.ctx, "Mushroom.lib = {}", lib);
+        }
+        return Ok(mushroom);
+    }
+
+    /// Whether `only` asks for the library alone, the head is matched first and always germinates its library too
+    pub fn lib_only(&self, mushroom: &Mushroom) -> Result<bool, GerminationError> {
+        let target = match &self.only {
+            Some(target) => target,
+            None => return Ok(false),
+        };
+        let head_name = mushroom.get_head_name();
+        let lib_name = mushroom.get_lib_name();
+        // The library is named after the head unless `lib-name:` says otherwise
+        if head_name.as_ref() == Some(target) && lib_name.as_ref() == Some(target) && mushroom.lib_name.is_none() {
+            return Err(GerminationError::AmbiguousTarget(target.clone()));
+        }
+        if head_name.as_ref() == Some(target) {
+            return Ok(false);
+        }
+        if lib_name.as_ref() == Some(target) {
+            return Ok(true);
+        }
+        return Err(GerminationError::UnknownTarget(target.clone()));
+    }
+
+    /// The rustc commands a germination would run, in order, without running them
+    pub fn germination_commands(&self) -> Result<Vec<Command>, GerminationError> {
+        let mushroom = self.pick_healthy_mushroom()?;
+        let lib_only = self.lib_only(&mushroom)?;
+        let rustc = toolchain::resolve_rustc(self, &mushroom)?;
+        // The build script isn't ran, its last output is what the next germination would most likely use
+        let build = build_script::cached_output(self, &mushroom);
+        let git = git_info::read_git_info(&self.cwd);
+        if build.is_none() {
+            if let Some(script) = build_script::find_build_script(self, &mushroom) {
+                warn!(&self.ctx, "Build script {} hasn't ran yet, its OUT_DIR, cfgs and environment are missing from these commands", script.display());
+            }
+        }
+        for (stage, hooks) in [(HookStage::PreBuild, &mushroom.pre_build), (HookStage::PostBuild, &mushroom.post_build)].iter() {
+            for hook in hooks.iter() {
+                warn!(&self.ctx, "{} hook would run and isn't shown: {}", stage.key(), hook);
+            }
+        }
+        let mut commands = Vec::new();
+        if let Some(lib_path) = self.get_mushroom_lib_path(&mushroom) {
+            commands.push(mushroom.create_lib_command(self, &rustc, &lib_path));
+        }
+        if !lib_only {
+            commands.push(mushroom.create_command(self, &rustc, &self.get_mushroom_new_head_path(&mushroom)));
+        }
+        for cmd in commands.iter_mut() {
+            if let Some(git) = &git {
+                cmd.envs(git.env());
+            }
+            if let Some(build) = &build {
+                build.apply(self, &mushroom, cmd);
+            }
+        }
+        return Ok(commands);
+    }
+
+    pub fn try_germinate(&self) -> Result<PathBuf, GerminationError> {
+        let mushroom = self.pick_healthy_mushroom()?;
+        let lib_only = self.lib_only(&mushroom)?;
+        let rustc = toolchain::resolve_rustc(self, &mushroom)?;
+        toolchain::check_rust_version(self, &mushroom, &rustc)?;
 
         let target_path = self.cwd.join(&mushroom.head);
         let target_dir  = target_path.parent();
@@ -121,29 +370,198 @@ impl Kinoko {
             }
         }
 
-        return match try_make_head_from_roots(&mushroom, &self) {
-            Ok(_) => Ok(if cfg!(windows) { PathBuf::from(format!("{}", target_path.display()).replace("/", "\\")) } else { target_path }),
-            Err(msg) => Err(GerminationError::GrowthFailure(msg)),
-        };
+        let target = hooks::hook_target(self, &mushroom, &rustc);
+        hooks::run_hooks(self, &mushroom, &rustc, &target, HookStage::PreBuild)?;
+        let build = build_script::run_build_script(self, &mushroom, &rustc)?;
+        // Read once so the library and the head are stamped with the same commit
+        let git = git_info::read_git_info(&self.cwd);
+        if mushroom.lib.is_some() {
+            try_make_lib_from_roots(&mushroom, &self, &rustc, &build, &git)?;
+        }
+        if let (true, Some(lib_path)) = (lib_only, self.get_mushroom_lib_path(&mushroom)) {
+            hooks::run_hooks(self, &mushroom, &rustc, &target, HookStage::PostBuild)?;
+            return Ok(lib_path);
+        }
+
+        try_make_head_from_roots(&mushroom, &self, &rustc, &build, &git)?;
+        hooks::run_hooks(self, &mushroom, &rustc, &target, HookStage::PostBuild)?;
+        return Ok(if cfg!(windows) { PathBuf::from(format!("{}", target_path.display()).replace("/", "\\")) } else { target_path });
     }
 }
 pub struct Mushroom {
     pub root: String,
     pub head: String,
+    /// Amount of successfully germinated heads kept in the history folder
+    pub history: usize,
+    /// Rust edition passed to rustc, rustc's own default (2015) when missing
+    pub edition: Option<String>,
+    /// Features always enabled as `--cfg feature="name"`
+    pub features: Vec<String>,
+    /// Named sets of extra rustc flags, written as `profile.<name>: <flags>`
+    pub profiles: Vec<(String, Vec<String>)>,
+    /// Root of a library compiled before the head and passed to it with `--extern`
+    pub lib: Option<String>,
+    /// Crate name of the library, defaults to the head's name
+    pub lib_name: Option<String>,
+    /// Compiler to germinate with instead of the `rustc` in the PATH
+    pub rustc: Option<String>,
+    /// Oldest rustc the mushroom germinates with, checked against `rustc -V`
+    pub rust_version: Option<String>,
+    /// Shell commands ran before rustc, one `pre-build:` line each
+    pub pre_build: Vec<String>,
+    /// Shell commands ran after a successful germination, one `post-build:` line each
+    pub post_build: Vec<String>,
+    /// Build script ran before rustc, a `build.rs` next to the kinoko.🍄 is used without it
+    pub build_script: Option<String>,
+    /// Package name for `KINOKO_PKG_NAME`, defaults to the head's name
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    /// Also sets the package variables as `CARGO_PKG_*` for code written for cargo
+    pub cargo_env: bool,
 }
 impl Mushroom {
     pub fn new() -> Mushroom {
         Mushroom {
             root: String::new(),
             head: String::new(),
+            history: head_history::DEFAULT_HISTORY_SIZE,
+            edition: None,
+            features: Vec::new(),
+            profiles: Vec::new(),
+            lib: None,
+            lib_name: None,
+            rustc: None,
+            rust_version: None,
+            pre_build: Vec::new(),
+            post_build: Vec::new(),
+            build_script: None,
+            name: None,
+            version: None,
+            authors: Vec::new(),
+            description: None,
+            cargo_env: false,
+        }
+    }
+
+    pub fn get_lib_name(&self) -> Option<String> {
+        self.lib.as_ref()?;
+        if let Some(name) = &self.lib_name {
+            return Some(name.clone());
+        }
+        let head_name = std::path::Path::new(&self.head).file_name()?.to_string_lossy().to_string();
+        return Some(head_name.replace('-', "_"));
+    }
+
+    /// File name of the head
+    pub fn get_head_name(&self) -> Option<String> {
+        return Some(std::path::Path::new(&self.head).file_name()?.to_string_lossy().to_string());
+    }
+
+    /// Names `kinoko build --only` accepts, the head's file name and the library's crate name
+    pub fn target_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.get_head_name().into_iter().collect();
+        if let Some(lib_name) = self.get_lib_name() {
+            if !names.contains(&lib_name) {
+                names.push(lib_name);
+            }
+        }
+        return names;
+    }
+
+    /// `name`, or the head's file name without one
+    pub fn get_package_name(&self) -> Option<String> {
+        if let Some(name) = &self.name {
+            return Some(name.clone());
+        }
+        return Some(std::path::Path::new(&self.head).file_name()?.to_string_lossy().to_string());
+    }
+
+    /// Environment variables describing the package while rustc runs, for `env!("KINOKO_PKG_VERSION")`
+    pub fn package_env(&self) -> Vec<(String, String)> {
+        let mut values = Vec::new();
+        if let Some(name) = self.get_package_name() {
+            values.push(("NAME", name));
+        }
+        if let Some(version) = &self.version {
+            values.push(("VERSION", version.clone()));
         }
+        if ! self.authors.is_empty() {
+            // Joined like cargo does
+            values.push(("AUTHORS", self.authors.join(":")));
+        }
+        if let Some(description) = &self.description {
+            values.push(("DESCRIPTION", description.clone()));
+        }
+        let mut env = Vec::new();
+        for (key, value) in values.into_iter() {
+            if self.cargo_env {
+                env.push((format!("CARGO_PKG_{}", key), value.clone()));
+            }
+            env.push((format!("KINOKO_PKG_{}", key), value));
+        }
+        return env;
+    }
+
+    pub fn get_profile(&self, name: &str) -> Option<&Vec<String>> {
+        return self.profiles.iter().find(|(profile, _)| profile == name).map(|(_, flags)| flags);
     }
 
     pub fn serialize(&self) -> String {
-        return format!("root: {}\nhead: {}", self.root, self.head);
+        let mut content = format!("root: {}\nhead: {}", self.root, self.head);
+        if self.history != head_history::DEFAULT_HISTORY_SIZE {
+            content.push_str(&format!("\nhistory: {}", self.history));
+        }
+        if let Some(edition) = &self.edition {
+            content.push_str(&format!("\nedition: {}", edition));
+        }
+        if ! self.features.is_empty() {
+            content.push_str(&format!("\nfeatures: {}", self.features.join(" ")));
+        }
+        for (name, flags) in self.profiles.iter() {
+            content.push_str(&format!("\nprofile.{}: {}", name, flags.join(" ")));
+        }
+        if let Some(lib) = &self.lib {
+            content.push_str(&format!("\nlib: {}", lib));
+        }
+        if let Some(lib_name) = &self.lib_name {
+            content.push_str(&format!("\nlib-name: {}", lib_name));
+        }
+        if let Some(rustc) = &self.rustc {
+            content.push_str(&format!("\nrustc: {}", rustc));
+        }
+        if let Some(rust_version) = &self.rust_version {
+            content.push_str(&format!("\nrust-version: {}", rust_version));
+        }
+        for hook in self.pre_build.iter() {
+            content.push_str(&format!("\npre-build: {}", hook));
+        }
+        for hook in self.post_build.iter() {
+            content.push_str(&format!("\npost-build: {}", hook));
+        }
+        if let Some(build_script) = &self.build_script {
+            content.push_str(&format!("\nbuild-script: {}", build_script));
+        }
+        if let Some(name) = &self.name {
+            content.push_str(&format!("\nname: {}", name));
+        }
+        if let Some(version) = &self.version {
+            content.push_str(&format!("\nversion: {}", version));
+        }
+        for author in self.authors.iter() {
+            content.push_str(&format!("\nauthors: {}", author));
+        }
+        if let Some(description) = &self.description {
+            content.push_str(&format!("\ndescription: {}", description));
+        }
+        if self.cargo_env {
+            content.push_str("\ncargo-env: true");
+        }
+        return content;
     }
 
-    pub fn deserialize<P: AsRef<std::path::Path>>(file: P) -> Option<Mushroom> {
+    pub fn deserialize<P: AsRef<std::path::Path>>(ctx: &Context, file: P) -> Option<Mushroom> {
         let file = file.as_ref().to_path_buf();
         if !file.is_file() {
             return None;
@@ -170,15 +588,79 @@ impl Mushroom {
                             mushroom.head = String::from(val);
                             has_head = true;
                         },
-                        _ => {}
+                        "history" => {
+                            match val.parse::<usize>() {
+                                Ok(size) => mushroom.history = size,
+                                Err(_) => warn!(ctx, "Mushroom history must be a positive number, got `{}`. Keeping {} heads", val, mushroom.history),
+                            }
+                        },
+                        "edition" => {
+                            mushroom.edition = Some(String::from(val));
+                        },
+                        "features" => {
+                            mushroom.features.extend(val.split_whitespace().map(String::from));
+                        },
+                        "lib" => {
+                            mushroom.lib = Some(String::from(val));
+                        },
+                        "lib-name" => {
+                            mushroom.lib_name = Some(String::from(val));
+                        },
+                        "rustc" => {
+                            mushroom.rustc = Some(String::from(val));
+                        },
+                        "name" => {
+                            mushroom.name = Some(String::from(val));
+                        },
+                        "version" => {
+                            mushroom.version = Some(String::from(val));
+                        },
+                        "authors" => {
+                            // One author per line, names may hold commas like `Doe, Jane <jane@x>`
+                            if !val.is_empty() {
+                                mushroom.authors.push(String::from(val));
+                            }
+                        },
+                        "description" => {
+                            mushroom.description = Some(String::from(val));
+                        },
+                        "cargo-env" => {
+                            match val {
+                                "true" => mushroom.cargo_env = true,
+                                "false" => mushroom.cargo_env = false,
+                                _ => warn!(ctx, "Mushroom cargo-env must be true or false, got `{}`", val),
+                            }
+                        },
+                        "build-script" => {
+                            mushroom.build_script = Some(String::from(val));
+                        },
+                        "pre-build" => {
+                            mushroom.pre_build.push(String::from(val));
+                        },
+                        "post-build" => {
+                            mushroom.post_build.push(String::from(val));
+                        },
+                        "rust-version" => {
+                            match toolchain::parse_version(val) {
+                                Some(_) => mushroom.rust_version = Some(String::from(val)),
+                                None => warn!(ctx, "Mushroom rust-version must look like 1.70 or 1.70.0, got `{}`. Not checking it", val),
+                            }
+                        },
+                        _ => {
+                            if let Some(name) = key.strip_prefix("profile.") {
+                                let flags = val.split_whitespace().map(String::from).collect();
+                                mushroom.profiles.retain(|(profile, _)| profile != name);
+                                mushroom.profiles.push((String::from(name), flags));
+                            }
+                        }
                     };
                 }
                 if ! has_root {
-                    error!("Mushroom has no root! Root is required to know where main function is located");
+                    error!(ctx, "Mushroom has no root! Root is required to know where main function is located");
                     return None;
                 }
                 if ! has_head {
-                    error!("Mushroom has no head! Defaulting to build/app");
+                    warn!(ctx, "Mushroom has no head! Defaulting to build/app");
                     #[cfg(target_family="windows")]
                     { mushroom.head = String::from("build\\app") };
                     #[cfg(target_family="unix")]
@@ -190,62 +672,150 @@ impl Mushroom {
         };
     }
 
-    pub fn create_command(&self, kinoko: &Kinoko) -> Command {
-        let mut cmd = Command::new("rustc");
-        cmd.arg("-o").arg({
-            let mut output = kinoko.cwd.join(&self.head);
-            #[cfg(target_family="windows")]
-            output.set_extension("exe");
-            output
-        }).arg({
+    /// Command compiling the head with `rustc`, see `toolchain::resolve_rustc`
+    pub fn create_command<S: AsRef<OsStr>>(&self, kinoko: &Kinoko, rustc: S, output: &Path) -> Command {
+        let mut cmd = Command::new(rustc);
+        cmd.arg("-o").arg(output).arg({
             kinoko.cwd.join(&self.root)
         });
+        if let (Some(lib_name), Some(lib_path)) = (self.get_lib_name(), kinoko.get_mushroom_lib_path(self)) {
+            cmd.arg("--extern").arg(format!("{}={}", lib_name, lib_path.display()));
+        }
+        self.add_shared_args(kinoko, &mut cmd);
+
+        cmd
+    }
+
+    /// Command compiling the mushroom's library, only meaningful when it has one
+    pub fn create_lib_command<S: AsRef<OsStr>>(&self, kinoko: &Kinoko, rustc: S, output: &Path) -> Command {
+        let mut cmd = Command::new(rustc);
+        cmd.arg("--crate-type").arg("lib");
+        if let Some(lib_name) = self.get_lib_name() {
+            cmd.arg("--crate-name").arg(lib_name);
+        }
+        cmd.arg("-o").arg(output);
+        if let Some(lib) = &self.lib {
+            cmd.arg(kinoko.cwd.join(lib));
+        }
+        self.add_shared_args(kinoko, &mut cmd);
+
+        cmd
+    }
+
+    fn add_shared_args(&self, kinoko: &Kinoko, cmd: &mut Command) {
+        if let Some(edition) = &self.edition {
+            cmd.arg("--edition").arg(edition);
+        }
+        for feature in self.features.iter() {
+            cmd.arg("--cfg").arg(format!("feature=\"{}\"", feature));
+        }
+        if let Some(profile) = kinoko.profile.as_ref().and_then(|name| self.get_profile(name)) {
+            cmd.args(profile);
+        }
         for arg in &kinoko.argv {
             cmd.arg(&arg);
         }
+        cmd.envs(self.package_env());
+    }
+}
 
-        cmd
+/// Prints a rustc command when asked to, otherwise it's only shown with `-vv`
+fn show_command(kinoko: &Kinoko, cmd: &Command) {
+    if kinoko.print_commands {
+        println!("{}", command_line(cmd));
+    } else {
+        debug!(&kinoko.ctx, "{}", command_line(cmd));
     }
 }
 
-fn try_make_head_from_roots(mushroom: &Mushroom, kinoko: &Kinoko) -> Result<(), String> {
-    if kinoko.mushroom_head_exists(&mushroom) {
-        let mhead_path = kinoko.get_mushroom_head_path(&mushroom);
-        let old_mhead_path = kinoko.get_mushroom_old_head_path(&mushroom);
-        path_move(mhead_path, old_mhead_path);
+fn try_make_lib_from_roots(mushroom: &Mushroom, kinoko: &Kinoko, rustc: &OsStr, build: &Option<BuildScriptOutput>, git: &Option<GitInfo>) -> Result<(), GerminationError> {
+    let lib_path = match kinoko.get_mushroom_lib_path(&mushroom) {
+        Some(lib_path) => lib_path,
+        None => return Ok(()),
+    };
+    let mut cmd = mushroom.create_lib_command(kinoko, rustc, &lib_path);
+    if let Some(git) = git {
+        cmd.envs(git.env());
+    }
+    if let Some(build) = build {
+        build.apply(kinoko, mushroom, &mut cmd);
+    }
+    show_command(kinoko, &cmd);
+    return match cmd.status() {
+        Err(err) => Err(GerminationError::RustcUnavailable(err)),
+        Ok(status) if status.success() => {
+            info!(&kinoko.ctx, "Germinated library: {}", lib_path.display());
+            Ok(())
+        },
+        Ok(_) => Err(GerminationError::GrowthFailure(format!("Library failed to compile: No germination done"))),
+    };
+}
+
+fn try_make_head_from_roots(mushroom: &Mushroom, kinoko: &Kinoko, rustc: &OsStr, build: &Option<BuildScriptOutput>, git: &Option<GitInfo>) -> Result<(), GerminationError> {
+    // rustc writes next to the head, the current head is only touched once the new one exists
+    let new_mhead_path = kinoko.get_mushroom_new_head_path(&mushroom);
+    let mut cmd = mushroom.create_command(kinoko, rustc, &new_mhead_path);
+    if let Some(git) = git {
+        cmd.envs(git.env());
     }
-    let mut cmd = mushroom.create_command(kinoko);
+    if let Some(build) = build {
+        build.apply(kinoko, mushroom, &mut cmd);
+    }
+    show_command(kinoko, &cmd);
     let result = cmd.status();
-    return match result {
+    let status = match result {
         Err(err) => {
-            restore_old_mushroom_head_if_exists(&mushroom, &kinoko);
-
-	   Err(format!("Failed to execute command: {}", err))
-        },
-        Ok(status) => {
-            if status.success() {
-                #[cfg(target_family="windows")]
-                info!("Germinated succesfully: {}.exe", mushroom.head);
-                #[cfg(target_family="unix")]
-                info!("Germinated succesfully: {}", mushroom.head);
-
-                Ok(())
-            } else {
-                restore_old_mushroom_head_if_exists(&mushroom, &kinoko);
-
-                Err(format!("Command failed: No germination done"))
-            }
+            discard_new_mushroom_head(&kinoko.ctx, &new_mhead_path);
+            return Err(GerminationError::RustcUnavailable(err));
         },
+        Ok(status) => status,
     };
+    if ! status.success() || ! new_mhead_path.is_file() {
+        discard_new_mushroom_head(&kinoko.ctx, &new_mhead_path);
+        return Err(GerminationError::GrowthFailure(format!("Command failed: No germination done")));
+    }
+
+    let mhead_path = kinoko.get_mushroom_head_path(&mushroom);
+    if kinoko.mushroom_head_exists(&mushroom) {
+        let old_mhead_path = kinoko.get_mushroom_old_head_path(&mushroom);
+        if let Err(err) = backup_mushroom_head(&mhead_path, &old_mhead_path) {
+            warn!(&kinoko.ctx, "Failed to back up old head to {}: {}", old_mhead_path.display(), err);
+        }
+    }
+    // Renaming over the head is atomic, at no point is the head missing
+    if ! path_move(&kinoko.ctx, &new_mhead_path, &mhead_path) {
+        discard_new_mushroom_head(&kinoko.ctx, &new_mhead_path);
+        return Err(GerminationError::HeadUnplaceable(mhead_path));
+    }
+
+    #[cfg(target_family="windows")]
+    info!(&kinoko.ctx, "Germinated succesfully: {}.exe", mushroom.head);
+    #[cfg(target_family="unix")]
+    info!(&kinoko.ctx, "Germinated succesfully: {}", mushroom.head);
+
+    if let Err(err) = head_history::record_head(&mushroom, &kinoko) {
+        warn!(&kinoko.ctx, "Failed to store germinated head in history: {}", err);
+    }
+
+    Ok(())
 }
 
-pub fn restore_old_mushroom_head_if_exists(mushroom: &Mushroom, kinoko: &Kinoko) -> bool {
-    let old_mhead_path = kinoko.get_mushroom_old_head_path(&mushroom);
-    if ! old_mhead_path.is_file() {
-        return false;
+/// Leaves the current head in place while keeping a copy of it as the old head
+fn backup_mushroom_head(mhead_path: &Path, old_mhead_path: &Path) -> std::io::Result<()> {
+    if old_mhead_path.is_file() {
+        fs::remove_file(old_mhead_path)?;
     }
-    let mhead_path = kinoko.get_mushroom_head_path(&mushroom);
-    info!("Restoring old head...");
-    return path_move(old_mhead_path, mhead_path);
+    if fs::hard_link(mhead_path, old_mhead_path).is_err() {
+        fs::copy(mhead_path, old_mhead_path)?;
+    }
+    return Ok(());
 }
 
+fn discard_new_mushroom_head(ctx: &Context, new_mhead_path: &Path) {
+    if ! new_mhead_path.exists() {
+        return;
+    }
+    if let Err(err) = fs::remove_file(new_mhead_path) {
+        warn!(ctx, "Failed to remove unfinished head {}: {}", new_mhead_path.display(), err);
+    }
+}
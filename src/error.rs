@@ -0,0 +1,104 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use data_structs::GerminationError;
+
+/// Exit codes of the kinoko CLI, each error category has its own so scripts can tell them apart
+pub mod exit_code {
+    /// Anything not covered by the other codes
+    pub const FAILURE: u8 = 1;
+    /// Unknown command, unknown option or bad option value
+    pub const USAGE: u8 = 2;
+    /// No kinoko.🍄 where one was expected
+    pub const NO_MUSHROOM: u8 = 3;
+    /// The kinoko.🍄 can't be read or points to things that don't exist
+    pub const INVALID_MUSHROOM: u8 = 4;
+    /// rustc ran and reported errors
+    pub const COMPILE_ERROR: u8 = 5;
+    /// rustc couldn't be started, or is older than the mushroom's `rust-version`
+    pub const RUSTC_NOT_FOUND: u8 = 6;
+    /// The project can't be planted as asked
+    pub const INIT_ERROR: u8 = 7;
+    /// Reading or writing files failed
+    pub const IO_ERROR: u8 = 8;
+    /// A `pre-build` or `post-build` command of the mushroom failed
+    pub const HOOK_FAILED: u8 = 9;
+}
+
+#[derive(Debug)]
+pub enum KinokoError {
+    /// Bad command line usage
+    Args(String),
+    Germination(GerminationError),
+    /// Invalid project names, unknown templates and the like
+    Init(String),
+    /// A filesystem operation failed, described by what was being done
+    Io(String, io::Error),
+    Other(String),
+}
+impl KinokoError {
+    pub fn io<S: Into<String>>(context: S, err: io::Error) -> KinokoError {
+        return KinokoError::Io(context.into(), err);
+    }
+
+    pub fn io_at(action: &str, path: &PathBuf, err: io::Error) -> KinokoError {
+        return KinokoError::Io(format!("Failed to {} {}", action, path.display()), err);
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        return match self {
+            KinokoError::Args(_) => exit_code::USAGE,
+            KinokoError::Germination(err) => match err {
+                GerminationError::MissingRoots(_) => exit_code::NO_MUSHROOM,
+                GerminationError::MushroomUnpickable(_) => exit_code::INVALID_MUSHROOM,
+                GerminationError::InvalidRoot(_) => exit_code::INVALID_MUSHROOM,
+                GerminationError::UnknownProfile(_) => exit_code::INVALID_MUSHROOM,
+                GerminationError::UnknownTarget(_) => exit_code::INVALID_MUSHROOM,
+                GerminationError::AmbiguousTarget(_) => exit_code::INVALID_MUSHROOM,
+                GerminationError::GrowthFailure(_) => exit_code::COMPILE_ERROR,
+                GerminationError::BuildScriptFailed(_) => exit_code::COMPILE_ERROR,
+                GerminationError::MissingBuildScript(_) => exit_code::INVALID_MUSHROOM,
+                GerminationError::RustcUnavailable(_) => exit_code::RUSTC_NOT_FOUND,
+                GerminationError::RustcMissing(_, _) => exit_code::RUSTC_NOT_FOUND,
+                GerminationError::ToolchainUnavailable(_, _) => exit_code::RUSTC_NOT_FOUND,
+                GerminationError::RustcTooOld(_, _) => exit_code::RUSTC_NOT_FOUND,
+                GerminationError::HookFailed(_, _, _) => exit_code::HOOK_FAILED,
+                GerminationError::NoHeadDir(_) => exit_code::IO_ERROR,
+                GerminationError::HeadUnplaceable(_) => exit_code::IO_ERROR,
+            },
+            KinokoError::Init(_) => exit_code::INIT_ERROR,
+            KinokoError::Io(_, _) => exit_code::IO_ERROR,
+            KinokoError::Other(_) => exit_code::FAILURE,
+        };
+    }
+
+    pub fn to_exit_code(&self) -> ExitCode {
+        return ExitCode::from(self.exit_code());
+    }
+}
+impl std::fmt::Display for KinokoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            KinokoError::Args(msg) => write!(f, "{}", msg),
+            KinokoError::Germination(err) => write!(f, "{}", err),
+            KinokoError::Init(msg) => write!(f, "{}", msg),
+            KinokoError::Io(context, err) => write!(f, "{}: {}", context, err),
+            KinokoError::Other(msg) => write!(f, "{}", msg),
+        };
+    }
+}
+impl std::error::Error for KinokoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            KinokoError::Germination(err) => Some(err),
+            KinokoError::Io(_, err) => Some(err),
+            _ => None,
+        };
+    }
+}
+impl From<GerminationError> for KinokoError {
+    fn from(err: GerminationError) -> KinokoError {
+        return KinokoError::Germination(err);
+    }
+}
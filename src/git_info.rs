@@ -0,0 +1,491 @@
+//! Commit, branch and dirty flag of the git repository a project is in, read from the `.git` folder
+//! with plain file I/O so it works where the git binary isn't installed
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use git_objects;
+
+pub struct GitInfo {
+    /// Full hash of the checked out commit
+    pub commit: String,
+    /// Branch checked out, `None` on a detached HEAD
+    pub branch: Option<String>,
+    /// A tracked file differs from the checked out commit, staged or not
+    pub dirty: bool,
+}
+impl GitInfo {
+    /// Variables set while rustc runs, for `env!("KINOKO_GIT_COMMIT")`
+    pub fn env(&self) -> Vec<(String, String)> {
+        return vec![
+            (String::from("KINOKO_GIT_COMMIT"), self.commit.clone()),
+            (String::from("KINOKO_GIT_SHORT_COMMIT"), self.short_commit().to_string()),
+            (String::from("KINOKO_GIT_BRANCH"), self.branch.clone().unwrap_or_default()),
+            (String::from("KINOKO_GIT_DIRTY"), self.dirty.to_string()),
+        ];
+    }
+
+    pub fn short_commit(&self) -> &str {
+        return &self.commit[..self.commit.len().min(7)];
+    }
+}
+
+/// Git details of the repository `dir` is in, `None` outside of one or before its first commit
+pub fn read_git_info(dir: &Path) -> Option<GitInfo> {
+    let (work_tree, git_dir) = find_git_dir(dir)?;
+    let common_dir = common_dir(&git_dir);
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let (commit, branch) = match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            let commit = resolve_ref(&git_dir, &common_dir, reference)?;
+            (commit, Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string()))
+        },
+        None => (head.to_string(), None),
+    };
+    if !is_hash(&commit) {
+        return None;
+    }
+    // SHA-256 repositories store 32 byte hashes in the index
+    let hash_len = commit.len() / 2;
+    let dirty = is_dirty(&git_dir, &common_dir, &work_tree, &commit, hash_len);
+    return Some(GitInfo { commit: commit, branch: branch, dirty: dirty });
+}
+
+/// Work tree and git folder of the closest `.git` above `dir`, a `.git` file points to the folder of a worktree or submodule
+fn find_git_dir(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    for candidate in dir.ancestors() {
+        let dot_git = candidate.join(".git");
+        if dot_git.is_dir() {
+            return Some((candidate.to_path_buf(), dot_git));
+        }
+        if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+            return Some((candidate.to_path_buf(), candidate.join(git_dir)));
+        }
+    }
+    return None;
+}
+
+/// Worktrees keep their own HEAD but share refs with the main repository
+fn common_dir(git_dir: &Path) -> PathBuf {
+    return match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    };
+}
+
+/// Hash a ref points to, from its loose file or the packed-refs
+fn resolve_ref(git_dir: &Path, common_dir: &Path, reference: &str) -> Option<String> {
+    for dir in [git_dir, common_dir].iter() {
+        if let Ok(content) = fs::read_to_string(dir.join(reference)) {
+            let content = content.trim();
+            return match content.strip_prefix("ref:") {
+                Some(target) => resolve_ref(git_dir, common_dir, target.trim()),
+                None => Some(content.to_string()),
+            };
+        }
+    }
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    return find_packed_ref(&packed, reference);
+}
+
+/// `<hash> <ref>` lines, `#` starts the header and `^` the commit of the previous annotated tag
+fn find_packed_ref(packed: &str, reference: &str) -> Option<String> {
+    for line in packed.lines() {
+        if line.starts_with('#') || line.starts_with('^') {
+            continue;
+        }
+        if let Some((hash, name)) = line.split_once(' ') {
+            if name.trim() == reference {
+                return Some(hash.to_string());
+            }
+        }
+    }
+    return None;
+}
+
+fn is_hash(text: &str) -> bool {
+    return (text.len() == 40 || text.len() == 64) && text.chars().all(|c| c.is_ascii_hexdigit());
+}
+
+/// An entry of the index, the staged version of a file
+struct IndexEntry {
+    path: Vec<u8>,
+    mode: u32,
+    mtime_secs: u32,
+    mtime_nanos: u32,
+    size: u32,
+    oid: Vec<u8>,
+    /// Non zero while a merge conflict is unresolved
+    stage: u16,
+    /// Files outside a sparse checkout aren't in the work tree
+    skip_worktree: bool,
+    /// Added with `git add -N`, in the index but not staged yet
+    intent_to_add: bool,
+}
+
+/// Compares the work tree with the index and the index with the tree of `commit`, like `git status` ignoring untracked
+/// files. Anything that can't be read counts as dirty so a build is never stamped clean by mistake
+fn is_dirty(git_dir: &Path, common_dir: &Path, work_tree: &Path, commit: &str, hash_len: usize) -> bool {
+    let index_path = git_dir.join("index");
+    let entries = match fs::read(&index_path).ok().and_then(|index| parse_index(&index, hash_len)) {
+        Some(entries) => entries,
+        None => return true,
+    };
+    if entries.iter().any(|entry| entry.stage != 0 || entry.intent_to_add) {
+        return true;
+    }
+    let index_mtime = fs::metadata(&index_path).ok().and_then(|meta| modified_since_epoch(&meta));
+    if work_tree_differs(&entries, work_tree, index_mtime, hash_len) {
+        return true;
+    }
+    let objects_dir = common_dir.join("objects");
+    let head_tree = git_objects::from_hex(commit).and_then(|commit| commit_tree(&objects_dir, &commit, hash_len));
+    return match (head_tree, tree_id(&entries, hash_len)) {
+        (Some(head_tree), Some(index_tree)) => head_tree != index_tree,
+        _ => true,
+    };
+}
+
+/// Entries of an index file of version 2, 3 or 4. `None` when it's malformed
+fn parse_index(index: &[u8], hash_len: usize) -> Option<Vec<IndexEntry>> {
+    if index.get(0..4)? != b"DIRC" {
+        return None;
+    }
+    let version = read_u32(index, 4)?;
+    if version < 2 || version > 4 {
+        return None;
+    }
+    let count = read_u32(index, 8)?;
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut offset = 12;
+    let mut path: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let entry_start = offset;
+        let mtime_secs = read_u32(index, offset + 8)?;
+        let mtime_nanos = read_u32(index, offset + 12)?;
+        let mode = read_u32(index, offset + 24)?;
+        let size = read_u32(index, offset + 36)?;
+        let oid = index.get(offset + 40..offset + 40 + hash_len)?.to_vec();
+        let flags = read_u16(index, offset + 40 + hash_len)?;
+        offset += 40 + hash_len + 2;
+        // Extended flags, only in version 3 and later
+        let mut extended = 0;
+        if version >= 3 && flags & 0x4000 != 0 {
+            extended = read_u16(index, offset)?;
+            offset += 2;
+        }
+        if version == 4 {
+            // The path drops a number of bytes from the end of the previous one and adds the rest
+            let (strip, read) = read_varint(index, offset)?;
+            offset += read;
+            let keep = path.len().checked_sub(strip as usize)?;
+            path.truncate(keep);
+            let end = offset + index.get(offset..)?.iter().position(|b| *b == 0)?;
+            path.extend_from_slice(&index[offset..end]);
+            offset = end + 1;
+        } else {
+            let end = offset + index.get(offset..)?.iter().position(|b| *b == 0)?;
+            path = index[offset..end].to_vec();
+            // Entries are padded with 1 to 8 NUL bytes to a multiple of 8
+            let entry_len = end - entry_start;
+            offset = entry_start + (entry_len + 8) / 8 * 8;
+        }
+        entries.push(IndexEntry {
+            path: path.clone(),
+            mode: mode,
+            mtime_secs: mtime_secs,
+            mtime_nanos: mtime_nanos,
+            size: size,
+            oid: oid,
+            stage: (flags >> 12) & 0x3,
+            skip_worktree: extended & 0x4000 != 0,
+            intent_to_add: extended & 0x2000 != 0,
+        });
+    }
+    return Some(entries);
+}
+
+/// A file differs when its content hashes to another blob than the staged one. Hashing is skipped when the size and
+/// modification time still match the index, unless the file changed in the same instant the index was written
+fn work_tree_differs(entries: &[IndexEntry], work_tree: &Path, index_mtime: Option<Duration>, hash_len: usize) -> bool {
+    for entry in entries.iter() {
+        // Gitlinks (submodules) are folders, their state is their own repository's business
+        if entry.mode & 0o170000 == 0o160000 || entry.skip_worktree {
+            continue;
+        }
+        let file = work_tree.join(String::from_utf8_lossy(&entry.path).as_ref());
+        let meta = match fs::symlink_metadata(&file) {
+            Ok(meta) => meta,
+            Err(_) => return true,
+        };
+        if meta.is_dir() || mode_differs(entry.mode, &meta) {
+            return true;
+        }
+        let modified = modified_since_epoch(&meta);
+        let racy = match (modified, index_mtime) {
+            (Some(modified), Some(index_mtime)) => modified >= index_mtime,
+            _ => true,
+        };
+        let stat_matches = match modified {
+            // Some filesystems and git builds don't keep nanoseconds
+            Some(modified) => meta.len() as u32 == entry.size && modified.as_secs() as u32 == entry.mtime_secs
+                && (entry.mtime_nanos == 0 || modified.subsec_nanos() == entry.mtime_nanos),
+            None => false,
+        };
+        if stat_matches && !racy {
+            continue;
+        }
+        let content = if meta.file_type().is_symlink() { link_target(&file) } else { fs::read(&file).ok() };
+        match content {
+            Some(content) if git_objects::object_id("blob", &content, hash_len) == entry.oid => {},
+            _ => return true,
+        }
+    }
+    return false;
+}
+
+fn modified_since_epoch(meta: &fs::Metadata) -> Option<Duration> {
+    return meta.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+}
+
+/// The executable bit and whether it's a symlink, the rest of the mode isn't tracked
+#[cfg(target_family="unix")]
+fn mode_differs(mode: u32, meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    return match mode & 0o170000 {
+        0o120000 => !meta.file_type().is_symlink(),
+        _ => meta.file_type().is_symlink() || (mode & 0o111 != 0) != (meta.permissions().mode() & 0o111 != 0),
+    };
+}
+
+#[cfg(not(target_family="unix"))]
+fn mode_differs(_mode: u32, _meta: &fs::Metadata) -> bool {
+    return false;
+}
+
+#[cfg(target_family="unix")]
+fn link_target(file: &Path) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    return fs::read_link(file).ok().map(|target| target.as_os_str().as_bytes().to_vec());
+}
+
+#[cfg(not(target_family="unix"))]
+fn link_target(file: &Path) -> Option<Vec<u8>> {
+    return fs::read_link(file).ok().map(|target| target.to_string_lossy().replace('\\', "/").into_bytes());
+}
+
+/// Id of the tree `git write-tree` would make from the index
+fn tree_id(entries: &[IndexEntry], hash_len: usize) -> Option<Vec<u8>> {
+    return subtree_id(entries, 0, hash_len);
+}
+
+/// Index order is tree order, so every folder is a run of entries sharing its prefix
+fn subtree_id(entries: &[IndexEntry], prefix_len: usize, hash_len: usize) -> Option<Vec<u8>> {
+    let mut content = Vec::new();
+    let mut idx = 0;
+    while idx < entries.len() {
+        let rest = entries[idx].path.get(prefix_len..)?;
+        match rest.iter().position(|b| *b == b'/') {
+            Some(slash) => {
+                let folder = &rest[..=slash];
+                let start = idx;
+                while idx < entries.len() && entries[idx].path.get(prefix_len..)?.starts_with(folder) {
+                    idx += 1;
+                }
+                // Sparse indexes store whole folders as one entry with a tree id
+                let oid = if idx - start == 1 && entries[start].path.len() == prefix_len + folder.len() {
+                    entries[start].oid.clone()
+                } else {
+                    subtree_id(&entries[start..idx], prefix_len + folder.len(), hash_len)?
+                };
+                content.extend_from_slice(b"40000 ");
+                content.extend_from_slice(&folder[..slash]);
+                content.push(0);
+                content.extend_from_slice(&oid);
+            },
+            None => {
+                let entry = &entries[idx];
+                content.extend_from_slice(format!("{:o} ", entry.mode).as_bytes());
+                content.extend_from_slice(rest);
+                content.push(0);
+                content.extend_from_slice(&entry.oid);
+                idx += 1;
+            },
+        }
+    }
+    return Some(git_objects::object_id("tree", &content, hash_len));
+}
+
+/// Tree id from the `tree <hash>` header of a commit object
+fn commit_tree(objects_dir: &Path, commit: &[u8], hash_len: usize) -> Option<Vec<u8>> {
+    let (kind, content) = git_objects::read_object(objects_dir, commit, hash_len)?;
+    if kind != git_objects::OBJ_COMMIT {
+        return None;
+    }
+    let header = String::from_utf8_lossy(&content);
+    let tree = header.lines().next()?.strip_prefix("tree ")?;
+    return git_objects::from_hex(tree.trim());
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let slice = bytes.get(offset..offset + 4)?;
+    return Some(u32::from_be_bytes([slice[0], slice[1], slice[2], slice[3]]));
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let slice = bytes.get(offset..offset + 2)?;
+    return Some(u16::from_be_bytes([slice[0], slice[1]]));
+}
+
+/// Git's offset varint, returns the value and how many bytes it took
+fn read_varint(bytes: &[u8], offset: usize) -> Option<(u64, usize)> {
+    let mut read = 0;
+    let mut byte = *bytes.get(offset)?;
+    read += 1;
+    let mut value = (byte & 0x7f) as u64;
+    while byte & 0x80 != 0 {
+        byte = *bytes.get(offset + read)?;
+        read += 1;
+        value = ((value + 1) << 7) | (byte & 0x7f) as u64;
+    }
+    return Some((value, read));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed part of an entry: stat data, mode 100644, the given size and oid, then the flags
+    fn entry_header(size: u32, oid_byte: u8, flags: u16) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x65, 0x00, 0x00, 0x00, 0, 0, 0, 7]);
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&0o100644u32.to_be_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&size.to_be_bytes());
+        bytes.extend_from_slice(&[oid_byte; 20]);
+        bytes.extend_from_slice(&flags.to_be_bytes());
+        return bytes;
+    }
+
+    fn index(version: u8, count: u8, entries: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![b'D', b'I', b'R', b'C', 0, 0, 0, version, 0, 0, 0, count];
+        for entry in entries.iter() {
+            bytes.extend_from_slice(entry);
+        }
+        // Trailing checksum, never read
+        bytes.extend_from_slice(&[0xeeu8; 20]);
+        return bytes;
+    }
+
+    #[test]
+    fn parses_version_2_entries_with_padding() {
+        // 62 byte header + "a.rs" + 6 NULs pads to 72, 62 + "src/main.rs" + 7 NULs pads to 80
+        let mut first = entry_header(3, 0xaa, 4);
+        first.extend_from_slice(b"a.rs\0\0\0\0\0\0");
+        let mut second = entry_header(10, 0xbb, 11);
+        second.extend_from_slice(b"src/main.rs\0\0\0\0\0\0\0");
+        let entries = parse_index(&index(2, 2, &[&first, &second]), 20).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, b"a.rs");
+        assert_eq!(entries[0].size, 3);
+        assert_eq!(entries[0].oid, vec![0xaa; 20]);
+        assert_eq!(entries[0].mtime_secs, 0x6500_0000);
+        assert_eq!(entries[0].mtime_nanos, 7);
+        assert_eq!(entries[1].path, b"src/main.rs");
+        assert_eq!(entries[1].mode, 0o100644);
+        assert_eq!(entries[1].stage, 0);
+    }
+
+    #[test]
+    fn parses_version_3_extended_flags_and_stages() {
+        // Extended bit set, intent-to-add in the extra 2 bytes: 64 + "new.rs" + 2 NULs is 72
+        let mut added = entry_header(0, 0xe6, 0x4000 | 6);
+        added.extend_from_slice(&[0x20, 0x00]);
+        added.extend_from_slice(b"new.rs\0\0");
+        // Stage 2 of a conflict, no extended flags: 62 + "x" + 1 NUL is 64
+        let mut conflict = entry_header(1, 0xcc, 0x2000 | 1);
+        conflict.extend_from_slice(b"x\0");
+        let entries = parse_index(&index(3, 2, &[&added, &conflict]), 20).unwrap();
+        assert!(entries[0].intent_to_add);
+        assert!(!entries[0].skip_worktree);
+        assert_eq!(entries[0].path, b"new.rs");
+        assert_eq!(entries[1].stage, 2);
+        assert!(!entries[1].intent_to_add);
+    }
+
+    #[test]
+    fn parses_version_4_prefix_compressed_paths() {
+        // No padding, each path strips bytes from the previous one
+        let mut first = entry_header(1, 0x01, 10);
+        first.extend_from_slice(b"\x00src/lib.rs\0");
+        let mut second = entry_header(2, 0x02, 11);
+        second.extend_from_slice(b"\x06main.rs\0");
+        let mut third = entry_header(3, 0x03, 9);
+        third.extend_from_slice(b"\x0btests/a.rs\0");
+        let entries = parse_index(&index(4, 3, &[&first, &second, &third]), 20).unwrap();
+        let paths: Vec<&[u8]> = entries.iter().map(|entry| entry.path.as_slice()).collect();
+        assert_eq!(paths, vec![&b"src/lib.rs"[..], &b"src/main.rs"[..], &b"tests/a.rs"[..]]);
+        assert_eq!(entries[2].size, 3);
+    }
+
+    #[test]
+    fn rejects_malformed_indexes() {
+        assert!(parse_index(b"DIRX\0\0\0\x02\0\0\0\0", 20).is_none());
+        assert!(parse_index(&index(5, 0, &[]), 20).is_none());
+        // Claims an entry it doesn't have
+        assert!(parse_index(&index(2, 1, &[]), 20).is_none());
+        // Strips more than the previous path had
+        let mut entry = entry_header(1, 0x01, 1);
+        entry.extend_from_slice(b"\x05a\0");
+        assert!(parse_index(&index(4, 1, &[&entry]), 20).is_none());
+    }
+
+    #[test]
+    fn reads_offset_varints() {
+        assert_eq!(read_varint(&[0x05], 0), Some((5, 1)));
+        assert_eq!(read_varint(&[0x7f], 0), Some((127, 1)));
+        // Unlike LEB128 each continuation adds one, so 0x80 0x00 is 128 and not 0
+        assert_eq!(read_varint(&[0x80, 0x00], 0), Some((128, 2)));
+        assert_eq!(read_varint(&[0xff, 0xff, 0x7f], 0), Some((2_113_663, 3)));
+        assert_eq!(read_varint(&[0x00, 0x81, 0x01], 1), Some((257, 2)));
+        assert_eq!(read_varint(&[0x80], 0), None);
+    }
+
+    #[test]
+    fn finds_packed_refs() {
+        let packed = "# pack-refs with: peeled fully-peeled sorted \n\
+            1111111111111111111111111111111111111111 refs/heads/main\n\
+            2222222222222222222222222222222222222222 refs/tags/v1.0\n\
+            ^3333333333333333333333333333333333333333\n";
+        assert_eq!(find_packed_ref(packed, "refs/heads/main").unwrap(), "1111111111111111111111111111111111111111");
+        assert_eq!(find_packed_ref(packed, "refs/tags/v1.0").unwrap(), "2222222222222222222222222222222222222222");
+        assert!(find_packed_ref(packed, "refs/heads/mai").is_none());
+        assert!(find_packed_ref(packed, "refs/heads/dev").is_none());
+    }
+
+    #[test]
+    fn tree_ids_match_git() {
+        let blob = git_objects::object_id("blob", b"hello\n", 20);
+        let entry = |path: &str, mode: u32| IndexEntry {
+            path: path.as_bytes().to_vec(),
+            mode: mode,
+            mtime_secs: 0,
+            mtime_nanos: 0,
+            size: 6,
+            oid: blob.clone(),
+            stage: 0,
+            skip_worktree: false,
+            intent_to_add: false,
+        };
+        assert_eq!(git_objects::to_hex(&tree_id(&[], 20).unwrap()), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+        // `a.txt` sorts before the `a/` folder in both the index and the tree
+        let entries = [entry("a.txt", 0o100644), entry("a/b.rs", 0o100755), entry("c", 0o100644)];
+        assert_eq!(git_objects::to_hex(&tree_id(&entries, 20).unwrap()), "0f68553f5c5bb38c763312e550ed069c299807cc");
+    }
+}
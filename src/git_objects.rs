@@ -0,0 +1,686 @@
+//! Just enough of git's object store to read a commit without the git binary: SHA-1 and SHA-256 object ids,
+//! zlib inflating, loose objects and version 2 pack indexes with their deltas
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const OBJ_COMMIT: u8 = 1;
+pub const OBJ_TREE: u8 = 2;
+pub const OBJ_BLOB: u8 = 3;
+pub const OBJ_TAG: u8 = 4;
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+/// Deltas on deltas deeper than this are treated as a broken pack
+const MAX_DELTA_DEPTH: usize = 64;
+
+/// Id of an object as git computes it, `hash_len` is 20 for SHA-1 repositories and 32 for SHA-256 ones
+pub fn object_id(kind: &str, content: &[u8], hash_len: usize) -> Vec<u8> {
+    let mut data = format!("{} {}\0", kind, content.len()).into_bytes();
+    data.extend_from_slice(content);
+    return if hash_len == 32 { sha256(&data).to_vec() } else { sha1(&data).to_vec() };
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    return (0..hex.len()).step_by(2).map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok()).collect();
+}
+
+/// Kind and content of an object, loose or packed
+pub fn read_object(objects_dir: &Path, oid: &[u8], hash_len: usize) -> Option<(u8, Vec<u8>)> {
+    return read_object_at_depth(objects_dir, oid, hash_len, 0);
+}
+
+fn read_object_at_depth(objects_dir: &Path, oid: &[u8], hash_len: usize, depth: usize) -> Option<(u8, Vec<u8>)> {
+    if depth > MAX_DELTA_DEPTH {
+        return None;
+    }
+    let hex = to_hex(oid);
+    if let Ok(compressed) = fs::read(objects_dir.join(&hex[..2]).join(&hex[2..])) {
+        return parse_loose(&inflate_zlib(&compressed)?);
+    }
+    for entry in fs::read_dir(objects_dir.join("pack")).ok()?.flatten() {
+        let idx_path = entry.path();
+        if idx_path.extension().map(|ext| ext != "idx").unwrap_or(true) {
+            continue;
+        }
+        let offset = match find_in_index(&fs::read(&idx_path).ok()?, oid, hash_len) {
+            Some(offset) => offset,
+            None => continue,
+        };
+        let mut pack = File::open(idx_path.with_extension("pack")).ok()?;
+        return read_packed(objects_dir, &mut pack, offset, hash_len, depth);
+    }
+    return None;
+}
+
+/// `<kind> <size>\0<content>`
+fn parse_loose(data: &[u8]) -> Option<(u8, Vec<u8>)> {
+    let nul = data.iter().position(|b| *b == 0)?;
+    let header = std::str::from_utf8(&data[..nul]).ok()?;
+    let (kind, size) = header.split_once(' ')?;
+    let kind = match kind {
+        "commit" => OBJ_COMMIT,
+        "tree" => OBJ_TREE,
+        "blob" => OBJ_BLOB,
+        "tag" => OBJ_TAG,
+        _ => return None,
+    };
+    let content = data[nul + 1..].to_vec();
+    if size.parse::<usize>().ok()? != content.len() {
+        return None;
+    }
+    return Some((kind, content));
+}
+
+/// Offset of an object in the pack of a version 2 `.idx`
+pub fn find_in_index(idx: &[u8], oid: &[u8], hash_len: usize) -> Option<u64> {
+    if idx.get(0..8)? != b"\xfftOc\x00\x00\x00\x02" {
+        return None;
+    }
+    let fanout = |byte: usize| -> Option<usize> { Some(read_u32(idx, 8 + byte * 4)? as usize) };
+    let total = fanout(255)?;
+    let first = *oid.first()? as usize;
+    let mut low = if first == 0 { 0 } else { fanout(first - 1)? };
+    let mut high = fanout(first)?;
+    let names = 8 + 256 * 4;
+    while low < high {
+        let mid = (low + high) / 2;
+        let name = idx.get(names + mid * hash_len..names + (mid + 1) * hash_len)?;
+        match name.cmp(oid) {
+            std::cmp::Ordering::Less => low = mid + 1,
+            std::cmp::Ordering::Greater => high = mid,
+            std::cmp::Ordering::Equal => {
+                let offsets = names + total * hash_len + total * 4;
+                let offset = read_u32(idx, offsets + mid * 4)?;
+                if offset & 0x8000_0000 == 0 {
+                    return Some(offset as u64);
+                }
+                // Packs over 2GiB keep the offset in a table of 8 byte ones
+                let large = offsets + total * 4 + (offset & 0x7fff_ffff) as usize * 8;
+                let high_half = read_u32(idx, large)? as u64;
+                return Some(high_half << 32 | read_u32(idx, large + 4)? as u64);
+            },
+        }
+    }
+    return None;
+}
+
+fn read_packed(objects_dir: &Path, pack: &mut File, offset: u64, hash_len: usize, depth: usize) -> Option<(u8, Vec<u8>)> {
+    if depth > MAX_DELTA_DEPTH {
+        return None;
+    }
+    pack.seek(SeekFrom::Start(offset)).ok()?;
+    // The entry header is at most a few bytes of size, then a base offset or id for deltas
+    let mut header = Vec::new();
+    pack.by_ref().take(64).read_to_end(&mut header).ok()?;
+    let mut pos = 0;
+    let mut byte = *header.get(pos)?;
+    pos += 1;
+    let kind = (byte >> 4) & 0x7;
+    let mut size = (byte & 0x0f) as u64;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = *header.get(pos)?;
+        pos += 1;
+        size |= ((byte & 0x7f) as u64) << shift;
+        shift += 7;
+    }
+    let base = match kind {
+        OBJ_OFS_DELTA => {
+            byte = *header.get(pos)?;
+            pos += 1;
+            let mut distance = (byte & 0x7f) as u64;
+            while byte & 0x80 != 0 {
+                byte = *header.get(pos)?;
+                pos += 1;
+                distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+            }
+            Some(read_packed(objects_dir, pack, offset.checked_sub(distance)?, hash_len, depth + 1)?)
+        },
+        OBJ_REF_DELTA => {
+            let base_oid = header.get(pos..pos + hash_len)?.to_vec();
+            pos += hash_len;
+            Some(read_object_at_depth(objects_dir, &base_oid, hash_len, depth + 1)?)
+        },
+        OBJ_COMMIT | OBJ_TREE | OBJ_BLOB | OBJ_TAG => None,
+        _ => return None,
+    };
+    // Deflate grows incompressible data by a few bytes per 16KiB block at most
+    let window = size + size / 64 + 1024;
+    pack.seek(SeekFrom::Start(offset + pos as u64)).ok()?;
+    let mut compressed = Vec::new();
+    pack.by_ref().take(window).read_to_end(&mut compressed).ok()?;
+    let data = inflate_zlib(&compressed)?;
+    if data.len() as u64 != size {
+        return None;
+    }
+    return match base {
+        Some((base_kind, base_data)) => Some((base_kind, apply_delta(&base_data, &data)?)),
+        None => Some((kind, data)),
+    };
+}
+
+/// Rebuilds an object from its base and a delta of copy and insert instructions
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let mut size = || -> Option<usize> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(pos)?;
+            pos += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    };
+    let base_size = size()?;
+    let result_size = size()?;
+    if base_size != base.len() {
+        return None;
+    }
+    let mut result = Vec::with_capacity(result_size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            let mut copy_offset = 0;
+            let mut copy_size = 0;
+            for bit in 0..7 {
+                if op & (1 << bit) == 0 {
+                    continue;
+                }
+                let byte = *delta.get(pos)? as usize;
+                pos += 1;
+                if bit < 4 {
+                    copy_offset |= byte << (bit * 8);
+                } else {
+                    copy_size |= byte << ((bit - 4) * 8);
+                }
+            }
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+            result.extend_from_slice(base.get(copy_offset..copy_offset + copy_size)?);
+        } else if op != 0 {
+            result.extend_from_slice(delta.get(pos..pos + op as usize)?);
+            pos += op as usize;
+        } else {
+            return None;
+        }
+    }
+    return if result.len() == result_size { Some(result) } else { None };
+}
+
+/// Inflates a zlib stream, anything after its end is ignored
+pub fn inflate_zlib(data: &[u8]) -> Option<Vec<u8>> {
+    let (cmf, flags) = (*data.get(0)?, *data.get(1)?);
+    // Deflate without a preset dictionary is all git writes
+    if cmf & 0x0f != 8 || ((cmf as u16) << 8 | flags as u16) % 31 != 0 || flags & 0x20 != 0 {
+        return None;
+    }
+    return inflate(&data[2..]);
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order the lengths of the code length alphabet are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u64,
+    count: u32,
+}
+impl<'a> BitReader<'a> {
+    /// Deflate packs bits starting from the least significant one
+    fn bits(&mut self, need: u32) -> Option<u32> {
+        while self.count < need {
+            self.buffer |= (*self.data.get(self.pos)? as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = (self.buffer & ((1u64 << need) - 1)) as u32;
+        self.buffer >>= need;
+        self.count -= need;
+        return Some(value);
+    }
+}
+
+/// Canonical Huffman code, as the amount of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for length in lengths.iter() {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+        return Huffman { counts: counts, symbols: symbols };
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).cloned();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return None;
+    }
+}
+
+/// Raw deflate as in RFC 1951
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = BitReader { data: data, pos: 0, buffer: 0, count: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                // Stored blocks start at a byte boundary
+                reader.buffer = 0;
+                reader.count = 0;
+                let len = u16::from_le_bytes([*data.get(reader.pos)?, *data.get(reader.pos + 1)?]) as usize;
+                let nlen = u16::from_le_bytes([*data.get(reader.pos + 2)?, *data.get(reader.pos + 3)?]) as usize;
+                if len != !nlen & 0xffff {
+                    return None;
+                }
+                reader.pos += 4;
+                out.extend_from_slice(data.get(reader.pos..reader.pos + len)?);
+                reader.pos += len;
+            },
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (symbol, length) in lengths.iter_mut().enumerate() {
+                    *length = match symbol {
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8,
+                    };
+                }
+                inflate_block(&mut reader, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5u8; 30]))?;
+            },
+            2 => {
+                let literal_count = reader.bits(5)? as usize + 257;
+                let distance_count = reader.bits(5)? as usize + 1;
+                let code_length_count = reader.bits(4)? as usize + 4;
+                let mut code_lengths = [0u8; 19];
+                for idx in 0..code_length_count {
+                    code_lengths[CODE_LENGTH_ORDER[idx]] = reader.bits(3)? as u8;
+                }
+                let code_length_code = Huffman::new(&code_lengths);
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (value, repeat) = match code_length_code.decode(&mut reader)? {
+                        symbol @ 0..=15 => (symbol as u8, 1),
+                        16 => (*lengths.last()?, 3 + reader.bits(2)?),
+                        17 => (0, 3 + reader.bits(3)?),
+                        18 => (0, 11 + reader.bits(7)?),
+                        _ => return None,
+                    };
+                    for _ in 0..repeat {
+                        lengths.push(value);
+                    }
+                }
+                if lengths.len() != literal_count + distance_count {
+                    return None;
+                }
+                let literals = Huffman::new(&lengths[..literal_count]);
+                let distances = Huffman::new(&lengths[literal_count..]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            },
+            _ => return None,
+        }
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Option<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Some(());
+        }
+        let idx = symbol - 257;
+        let length = *LENGTH_BASE.get(idx)? as usize + reader.bits(*LENGTH_EXTRA.get(idx)? as u32)? as usize;
+        let idx = distances.decode(reader)? as usize;
+        let distance = *DISTANCE_BASE.get(idx)? as usize + reader.bits(*DISTANCE_EXTRA.get(idx)? as u32)? as usize;
+        let start = out.len().checked_sub(distance)?;
+        // Copies may overlap what they write, byte by byte repeats the pattern
+        for offset in 0..length {
+            let byte = out[start + offset];
+            out.push(byte);
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let slice = bytes.get(offset..offset + 4)?;
+    return Some(u32::from_be_bytes([slice[0], slice[1], slice[2], slice[3]]));
+}
+
+/// Message padded to 64 byte blocks with its length in bits at the end, as both SHA-1 and SHA-256 do
+fn padded_tail(data: &[u8]) -> Vec<u8> {
+    let mut tail = data[data.len() / 64 * 64..].to_vec();
+    tail.push(0x80);
+    while tail.len() % 64 != 56 {
+        tail.push(0);
+    }
+    tail.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    return tail;
+}
+
+fn words(block: &[u8]) -> [u32; 16] {
+    let mut words = [0u32; 16];
+    for (idx, word) in words.iter_mut().enumerate() {
+        *word = u32::from_be_bytes([block[idx * 4], block[idx * 4 + 1], block[idx * 4 + 2], block[idx * 4 + 3]]);
+    }
+    return words;
+}
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let tail = padded_tail(data);
+    for block in data[..data.len() / 64 * 64].chunks(64).chain(tail.chunks(64)) {
+        let mut w = [0u32; 80];
+        w[..16].copy_from_slice(&words(block));
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (state[0], state[1], state[2], state[3], state[4]);
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *value = value.wrapping_add(*add);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (idx, value) in state.iter().enumerate() {
+        digest[idx * 4..idx * 4 + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    return digest;
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    let tail = padded_tail(data);
+    for block in data[..data.len() / 64 * 64].chunks(64).chain(tail.chunks(64)) {
+        let mut w = [0u32; 64];
+        w[..16].copy_from_slice(&words(block));
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let mut v = state;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let choice = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let temp1 = v[7].wrapping_add(s1).wrapping_add(choice).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let majority = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let temp2 = s0.wrapping_add(majority);
+            v = [temp1.wrapping_add(temp2), v[0], v[1], v[2], v[3].wrapping_add(temp1), v[4], v[5], v[6]];
+        }
+        for (value, add) in state.iter_mut().zip(v.iter()) {
+            *value = value.wrapping_add(*add);
+        }
+    }
+    let mut digest = [0u8; 32];
+    for (idx, value) in state.iter().enumerate() {
+        digest[idx * 4..idx * 4 + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    return digest;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_known_digests() {
+        assert_eq!(to_hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(to_hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // Crosses a block boundary with its padding
+        assert_eq!(to_hex(&sha1(&[b'a'; 64])), "0098ba824b5c16427bd7a1122a5a442a25ec644d");
+    }
+
+    #[test]
+    fn sha256_known_digests() {
+        assert_eq!(to_hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(to_hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+
+    #[test]
+    fn hashes_messages_over_several_blocks() {
+        // 56 bytes leaves no room for the length, the padding takes a second block
+        let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(to_hex(&sha1(two_blocks)), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+        assert_eq!(to_hex(&sha256(two_blocks)), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(to_hex(&sha1(&vec![b'a'; 1_000_000])), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+        assert_eq!(to_hex(&sha256(&[b'a'; 1000])), "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+    }
+
+    #[test]
+    fn object_ids_match_git() {
+        assert_eq!(to_hex(&object_id("blob", b"hello\n", 20)), "ce013625030ba8dba906f756967f9e9ca394464a");
+        assert_eq!(to_hex(&object_id("tree", b"", 20)), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+    }
+
+    #[test]
+    fn inflates_fixed_and_stored_blocks() {
+        // zlib.compress(b"hello")
+        let fixed = [0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x06, 0x2c, 0x02, 0x15];
+        assert_eq!(inflate_zlib(&fixed).unwrap(), b"hello");
+        // zlib.compress(b"hi", 0)
+        let stored = [0x78, 0x01, 0x01, 0x02, 0x00, 0xfd, 0xff, b'h', b'i', 0x01, 0x3b, 0x00, 0xd2];
+        assert_eq!(inflate_zlib(&stored).unwrap(), b"hi");
+        assert!(inflate_zlib(&[0x78, 0x9c, 0xff]).is_none());
+    }
+
+    #[test]
+    fn inflates_back_references_and_dynamic_blocks() {
+        // zlib.compress(data, 9), a fixed block whose copies overlap what they write
+        let repeated: Vec<u8> = (0..200u32).map(|i| b'a' + (i * i % 7) as u8).collect();
+        let fixed = [0x78, 0xda, 0x4b, 0x4c, 0x4a, 0x4d, 0x4e, 0x4e, 0x4d, 0x4a, 0x1c, 0xea, 0x14, 0x00, 0x5e, 0xcb, 0x4d, 0x58];
+        assert_eq!(inflate_zlib(&fixed).unwrap(), repeated);
+        // zlib with Z_HUFFMAN_ONLY, a dynamic block with its own code lengths
+        let dynamic = [
+            0x78, 0x01, 0x05, 0xc1, 0x01, 0x01, 0x00, 0x00, 0x08, 0xc3, 0xa0, 0xac, 0xec, 0xf6, 0xcf,
+            0x20, 0x00, 0x00, 0x40, 0x55, 0x55, 0x6d, 0xdb, 0xee, 0x01, 0xfd, 0x1c, 0x0d, 0xbc,
+        ];
+        assert_eq!(inflate_zlib(&dynamic).unwrap(), b"aaaaaaaaaaaaaaaaaaaabbbbbbbbbbcccccd");
+        // A distance reaching before the start of the output
+        assert!(inflate_zlib(&fixed[..6]).is_none());
+    }
+
+    #[test]
+    fn applies_copy_and_insert_deltas() {
+        // Base 11 bytes, result 12: copy 6 bytes from 0, insert "kinoko"
+        let delta = [11, 12, 0x90, 6, 6, b'k', b'i', b'n', b'o', b'k', b'o'];
+        assert_eq!(apply_delta(b"hello world", &delta).unwrap(), b"hello kinoko");
+        assert!(apply_delta(b"short", &delta).is_none());
+    }
+
+    #[test]
+    fn applies_deltas_with_offsets_and_default_sizes() {
+        let base: Vec<u8> = (0..0x10100u32).map(|i| (i % 251) as u8).collect();
+        // Sizes are 7 bit little endian varints, 0x10100 takes 3 bytes and 0x10002 too
+        let mut delta = vec![0x80, 0x82, 0x04, 0x82, 0x80, 0x04];
+        // Copy from offset 0x100 (second offset byte only) with no size bytes, meaning 0x10000
+        delta.extend_from_slice(&[0x82, 0x01]);
+        delta.extend_from_slice(&[2, b'!', b'?']);
+        let result = apply_delta(&base, &delta).unwrap();
+        assert_eq!(result.len(), 0x10002);
+        assert_eq!(&result[..0x10000], &base[0x100..]);
+        assert_eq!(&result[0x10000..], b"!?");
+        // Copying past the end of the base
+        assert!(apply_delta(b"abc", &[3, 4, 0x91, 1, 4]).is_none());
+        // Opcode 0 is reserved
+        assert!(apply_delta(b"abc", &[3, 1, 0]).is_none());
+    }
+
+    /// zlib stream of a single stored block
+    fn stored_zlib(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x78, 0x01, 0x01];
+        bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+        bytes.extend_from_slice(data);
+        let (mut a, mut b) = (1u32, 0u32);
+        for byte in data.iter() {
+            a = (a + *byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        bytes.extend_from_slice(&(b << 16 | a).to_be_bytes());
+        return bytes;
+    }
+
+    /// Type and size of a pack entry, 4 bits of size in the first byte and 7 in the next ones
+    fn pack_entry_header(kind: u8, mut size: usize) -> Vec<u8> {
+        let mut bytes = vec![kind << 4 | (size & 0x0f) as u8];
+        size >>= 4;
+        while size != 0 {
+            *bytes.last_mut().unwrap() |= 0x80;
+            bytes.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        return bytes;
+    }
+
+    #[test]
+    fn reads_loose_and_packed_objects() {
+        let objects_dir = std::env::temp_dir().join(format!("kinoko-git-objects-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&objects_dir);
+        fs::create_dir_all(objects_dir.join("pack")).unwrap();
+
+        let loose_oid = object_id("blob", b"loose\n", 20);
+        let hex = to_hex(&loose_oid);
+        fs::create_dir_all(objects_dir.join(&hex[..2])).unwrap();
+        fs::write(objects_dir.join(&hex[..2]).join(&hex[2..]), stored_zlib(b"blob 6\0loose\n")).unwrap();
+
+        // A blob and a delta on it at a negative offset: keep its first 20 bytes, then insert "!"
+        let base = b"fn main() { println!(\"spore\"); }\n";
+        let delta = [base.len() as u8, 21, 0x90, 20, 1, b'!'];
+        let mut pack = b"PACK\x00\x00\x00\x02\x00\x00\x00\x02".to_vec();
+        pack.extend_from_slice(&pack_entry_header(OBJ_BLOB, base.len()));
+        pack.extend_from_slice(&stored_zlib(base));
+        let delta_offset = pack.len();
+        pack.extend_from_slice(&pack_entry_header(OBJ_OFS_DELTA, delta.len()));
+        pack.push((delta_offset - 12) as u8);
+        pack.extend_from_slice(&stored_zlib(&delta));
+
+        let base_oid = object_id("blob", base, 20);
+        let mut result = base[..20].to_vec();
+        result.push(b'!');
+        let delta_oid = object_id("blob", &result, 20);
+        let mut entries = vec![(base_oid.clone(), 12u32), (delta_oid.clone(), delta_offset as u32)];
+        entries.sort();
+        let mut idx = b"\xfftOc\x00\x00\x00\x02".to_vec();
+        for byte in 0..256 {
+            let count = entries.iter().filter(|(oid, _)| (oid[0] as usize) <= byte).count() as u32;
+            idx.extend_from_slice(&count.to_be_bytes());
+        }
+        for (oid, _) in entries.iter() {
+            idx.extend_from_slice(oid);
+        }
+        idx.extend_from_slice(&[0u8; 8]);
+        for (_, offset) in entries.iter() {
+            idx.extend_from_slice(&offset.to_be_bytes());
+        }
+        fs::write(objects_dir.join("pack").join("pack-test.pack"), &pack).unwrap();
+        fs::write(objects_dir.join("pack").join("pack-test.idx"), &idx).unwrap();
+
+        assert_eq!(read_object(&objects_dir, &loose_oid, 20), Some((OBJ_BLOB, b"loose\n".to_vec())));
+        assert_eq!(read_object(&objects_dir, &base_oid, 20), Some((OBJ_BLOB, base.to_vec())));
+        assert_eq!(read_object(&objects_dir, &delta_oid, 20), Some((OBJ_BLOB, result)));
+        assert_eq!(read_object(&objects_dir, &[0x42; 20], 20), None);
+        let _ = fs::remove_dir_all(&objects_dir);
+    }
+
+    #[test]
+    fn finds_objects_in_a_pack_index() {
+        let oids = [[0x11u8; 20], [0x11u8; 20], [0xabu8; 20]];
+        let mut oids = oids.to_vec();
+        oids[1][19] = 0x12;
+        let mut idx = b"\xfftOc\x00\x00\x00\x02".to_vec();
+        for byte in 0..256 {
+            let count = oids.iter().filter(|oid| (oid[0] as usize) <= byte).count() as u32;
+            idx.extend_from_slice(&count.to_be_bytes());
+        }
+        for oid in oids.iter() {
+            idx.extend_from_slice(oid);
+        }
+        idx.extend_from_slice(&[0u8; 12]);
+        for offset in [12u32, 300, 0x8000_0000].iter() {
+            idx.extend_from_slice(&offset.to_be_bytes());
+        }
+        idx.extend_from_slice(&0x1_0000_0000u64.to_be_bytes());
+        assert_eq!(find_in_index(&idx, &oids[0], 20), Some(12));
+        assert_eq!(find_in_index(&idx, &oids[1], 20), Some(300));
+        assert_eq!(find_in_index(&idx, &oids[2], 20), Some(0x1_0000_0000));
+        assert_eq!(find_in_index(&idx, &[0x12u8; 20], 20), None);
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::hash::Hasher;
+
+use data_structs::{Kinoko, Mushroom};
+use utility::{info, warn, verbose};
+
+pub const DEFAULT_HISTORY_SIZE: usize = 3;
+
+/// A previously germinated head stored in the history folder.
+/// Stored as `<nanoseconds>-<fingerprint>-<head file name>`
+pub struct HistoryEntry {
+    pub path: PathBuf,
+    /// Unix time the head was germinated at, in seconds
+    pub timestamp: u64,
+    /// Unix time in nanoseconds, kept increasing between entries so they sort in build order
+    pub nanos: u64,
+    pub fingerprint: String,
+}
+impl HistoryEntry {
+    fn from_path(path: PathBuf) -> Option<HistoryEntry> {
+        let file_name = path.file_name()?.to_str()?.to_string();
+        let mut parts = file_name.splitn(3, '-');
+        let nanos = parts.next()?.parse::<u64>().ok()?;
+        let fingerprint = parts.next()?.to_string();
+        parts.next()?;
+        return Some(HistoryEntry {
+            path: path,
+            timestamp: nanos / 1_000_000_000,
+            nanos: nanos,
+            fingerprint: fingerprint,
+        });
+    }
+
+    pub fn matches_file<P: AsRef<Path>>(&self, other: P) -> bool {
+        let other = other.as_ref();
+        let same_len = match (fs::metadata(&self.path), fs::metadata(other)) {
+            (Ok(a), Ok(b)) => a.len() == b.len(),
+            _ => false,
+        };
+        if ! same_len {
+            return false;
+        }
+        return match (fs::read(&self.path), fs::read(other)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        };
+    }
+}
+
+/// 64 bit FNV-1a, unlike `DefaultHasher` it gives the same fingerprint on every rust release
+struct Fnv1a(u64);
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        return self.0;
+    }
+}
+
+/// Hashes the mushroom file and every rust file found next to the root
+pub fn source_fingerprint(mushroom: &Mushroom, kinoko: &Kinoko) -> String {
+    let mut hasher = Fnv1a(0xcbf29ce484222325);
+    if let Ok(content) = fs::read(kinoko.get_mushroom_path()) {
+        hasher.write(&content);
+    }
+    let root_path = kinoko.cwd.join(&mushroom.root);
+    let source_dir = match root_path.parent() {
+        Some(dir) => dir.to_path_buf(),
+        None => kinoko.cwd.clone(),
+    };
+    let mut sources = Vec::new();
+    collect_rust_files(&source_dir, &mut sources, 0);
+    sources.sort();
+    for source in sources.iter() {
+        if let Ok(content) = fs::read(source) {
+            hasher.write(source.strip_prefix(&source_dir).unwrap_or(source).to_string_lossy().as_bytes());
+            hasher.write(&content);
+        }
+    }
+    return format!("{:016x}", hasher.finish());
+}
+
+fn collect_rust_files(dir: &Path, sources: &mut Vec<PathBuf>, recursion_level: usize) {
+    if recursion_level > 8 {
+        return;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_rust_files(&path, sources, recursion_level + 1);
+        } else if path.extension().map(|ext| ext == "rs").unwrap_or(false) {
+            sources.push(path);
+        }
+    }
+}
+
+/// Lists the stored heads, newest first
+pub fn list_heads(mushroom: &Mushroom, kinoko: &Kinoko) -> io::Result<Vec<HistoryEntry>> {
+    let history_dir = kinoko.get_mushroom_history_dir(mushroom);
+    let mut heads = Vec::new();
+    if ! history_dir.is_dir() {
+        return Ok(heads);
+    }
+    for entry in fs::read_dir(&history_dir)? {
+        let entry = entry?;
+        if ! entry.path().is_file() {
+            continue;
+        }
+        if let Some(head) = HistoryEntry::from_path(entry.path()) {
+            heads.push(head);
+        }
+    }
+    heads.sort_by(|a, b| b.nanos.cmp(&a.nanos));
+    return Ok(heads);
+}
+
+/// Copies the current head into the history folder and drops the heads that exceed the mushroom's history size
+pub fn record_head(mushroom: &Mushroom, kinoko: &Kinoko) -> io::Result<()> {
+    if mushroom.history == 0 {
+        return Ok(());
+    }
+    let head_path = kinoko.get_mushroom_head_path(mushroom);
+    let head_name = match head_path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "head has no file name")),
+    };
+    let history_dir = kinoko.get_mushroom_history_dir(mushroom);
+    fs::create_dir_all(&history_dir)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+    // Two builds in the same instant, or a clock set back, still get a later entry than the newest one
+    let nanos = match list_heads(mushroom, kinoko)?.first() {
+        Some(newest) => now.max(newest.nanos + 1),
+        None => now,
+    };
+    let fingerprint = source_fingerprint(mushroom, kinoko);
+    let entry_path = history_dir.join(format!("{}-{}-{}", nanos, fingerprint, head_name));
+    fs::copy(&head_path, &entry_path)?;
+    verbose!(&kinoko.ctx, "Stored head in history: {}", entry_path.display());
+
+    let heads = list_heads(mushroom, kinoko)?;
+    for stale in heads.iter().skip(mushroom.history) {
+        if let Err(err) = fs::remove_file(&stale.path) {
+            warn!(&kinoko.ctx, "Failed to remove old head {}: {}", stale.path.display(), err);
+        }
+    }
+    return Ok(());
+}
+
+/// Puts a stored head back in place of the current head
+pub fn restore_head(entry: &HistoryEntry, mushroom: &Mushroom, kinoko: &Kinoko) -> io::Result<()> {
+    let head_path = kinoko.get_mushroom_head_path(mushroom);
+    if let Some(head_dir) = head_path.parent() {
+        fs::create_dir_all(head_dir)?;
+    }
+    // Copy next to the head first so the swap is a single rename
+    let mut staged_name = head_path.file_name().unwrap_or_default().to_os_string();
+    staged_name.push(".rollback");
+    let staged_path = head_path.with_file_name(staged_name);
+    fs::copy(&entry.path, &staged_path)?;
+    if let Err(err) = fs::rename(&staged_path, &head_path) {
+        let _ = fs::remove_file(&staged_path);
+        return Err(err);
+    }
+    info!(&kinoko.ctx, "Restored head from {}", entry.path.display());
+    return Ok(());
+}
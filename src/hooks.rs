@@ -0,0 +1,108 @@
+//! `pre-build:` and `post-build:` commands of a mushroom, ran through the shell from the project folder
+use std::ffi::{OsStr, OsString};
+use std::process::Command;
+
+use data_structs::{Kinoko, Mushroom, GerminationError};
+use utility::{info, debug, command_line};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    /// Before rustc runs, a failure stops the germination
+    PreBuild,
+    /// After the head is in place, a failure is reported and the new head kept
+    PostBuild,
+}
+impl HookStage {
+    /// Key of the commands in the kinoko.🍄
+    pub fn key(&self) -> &'static str {
+        return match self {
+            HookStage::PreBuild => "pre-build",
+            HookStage::PostBuild => "post-build",
+        };
+    }
+}
+
+/// Runs the mushroom's hooks of a stage in order, stopping at the first one failing.
+/// `target` is the one from `hook_target`, shared by both stages
+pub fn run_hooks(kinoko: &Kinoko, mushroom: &Mushroom, rustc: &OsStr, target: &OsStr, stage: HookStage) -> Result<(), GerminationError> {
+    let hooks = match stage {
+        HookStage::PreBuild => &mushroom.pre_build,
+        HookStage::PostBuild => &mushroom.post_build,
+    };
+    if hooks.is_empty() {
+        return Ok(());
+    }
+    for hook in hooks.iter() {
+        info!(&kinoko.ctx, "Running {} hook: {}", stage.key(), hook);
+        let mut cmd = shell_command(hook);
+        cmd.current_dir(&kinoko.cwd)
+            .env("KINOKO_PROJECT_DIR", &kinoko.cwd)
+            .env("KINOKO_ROOT", kinoko.cwd.join(&mushroom.root))
+            .env("KINOKO_HEAD", kinoko.get_mushroom_head_path(mushroom))
+            .env("KINOKO_PROFILE", kinoko.profile.clone().unwrap_or_default())
+            .env("KINOKO_TARGET", target)
+            .env("KINOKO_RUSTC", rustc);
+        if let Some(lib_path) = kinoko.get_mushroom_lib_path(mushroom) {
+            cmd.env("KINOKO_LIB", lib_path);
+        }
+        debug!(&kinoko.ctx, "{}", command_line(&cmd));
+        let failure = match cmd.status() {
+            Ok(status) if status.success() => continue,
+            Ok(status) => match status.code() {
+                Some(code) => format!("exited with {}", code),
+                None => String::from("terminated by a signal"),
+            },
+            Err(err) => format!("failed to start: {}", err),
+        };
+        return Err(GerminationError::HookFailed(stage, hook.clone(), failure));
+    }
+    return Ok(());
+}
+
+#[cfg(target_family="windows")]
+fn shell_command(hook: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(hook);
+    return cmd;
+}
+
+#[cfg(not(target_family="windows"))]
+fn shell_command(hook: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(hook);
+    return cmd;
+}
+
+/// Target the hooks get as `KINOKO_TARGET`, empty without any hook so rustc isn't asked for nothing
+pub fn hook_target(kinoko: &Kinoko, mushroom: &Mushroom, rustc: &OsStr) -> OsString {
+    if mushroom.pre_build.is_empty() && mushroom.post_build.is_empty() {
+        return OsString::new();
+    }
+    return target_triple(kinoko, mushroom, rustc);
+}
+
+/// `--target` of the rustc arguments, or the host rustc compiles for without one
+fn target_triple(kinoko: &Kinoko, mushroom: &Mushroom, rustc: &OsStr) -> OsString {
+    let profile = kinoko.profile.as_ref().and_then(|name| mushroom.get_profile(name));
+    let args: Vec<&String> = profile.into_iter().flatten().chain(kinoko.argv.iter()).collect();
+    for (idx, arg) in args.iter().enumerate() {
+        if let Some(target) = arg.strip_prefix("--target=") {
+            return OsString::from(target);
+        }
+        if arg.as_str() == "--target" {
+            if let Some(target) = args.get(idx + 1) {
+                return OsString::from(target.as_str());
+            }
+        }
+    }
+    // rustc -vV prints a `host: x86_64-unknown-linux-gnu` line
+    let output = match Command::new(rustc).arg("-vV").output() {
+        Ok(output) => output,
+        Err(_) => return OsString::new(),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    return stdout.lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(|host| OsString::from(host.trim()))
+        .unwrap_or_default();
+}
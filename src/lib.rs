@@ -0,0 +1,29 @@
+//! Kinoko as a library, germinates a kinoko.🍄 project without going through the CLI.
+//! Nothing here reads the process arguments or current directory, everything comes through the builder
+//! and an optional `Context`, so several projects can be germinated from the same process:
+//!
+//! ```no_run
+//! extern crate kinoko;
+//!
+//! let head = kinoko::Kinoko::at("path/to/project")
+//!     .context(kinoko::Context::new("/path/to/workspace"))
+//!     .profile("release")
+//!     .extra_args(vec!["-C".to_string(), "target-cpu=native".to_string()])
+//!     .germinate();
+//! ```
+
+pub mod utility;
+pub mod context;
+pub mod data_structs;
+pub mod head_history;
+pub mod error;
+pub mod rust_project;
+pub mod toolchain;
+pub mod hooks;
+pub mod build_script;
+pub mod git_info;
+mod git_objects;
+
+pub use data_structs::{Kinoko, KinokoBuilder, Mushroom, GerminationError};
+pub use error::KinokoError;
+pub use context::Context;
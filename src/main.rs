@@ -5,79 +5,165 @@ use std::fs::{self, DirEntry};
 use std::io;
 use std::rc::Rc;
 
+extern crate kinoko;
+
 // Helper
-mod utility;
-mod data_structs;
+mod templates;
+mod licenses;
+mod user_config;
+mod toml_subset;
+mod subcommand;
+mod args;
+mod plugins;
 
-use utility::*;
+use kinoko::utility::*;
+use kinoko::error::exit_code;
+use kinoko::context::Context;
 
 // Commands
 mod cmd_init;
 mod cmd_build;
+mod cmd_history;
+mod cmd_rollback;
+mod cmd_template;
+mod cmd_export;
+mod cmd_import;
+mod cmd_ide;
+mod cmd_info;
+mod cmd_completions;
+mod cmd_man;
+
+use subcommand::{Registry, apply_global_options, global_options};
+use args::{ArgSpec, ArgError};
 
 macro_rules! print_cmd_usage {
     ($cmd: expr) => {
-	println!(" {:<50}--     {}", ($cmd).0, ($cmd).1)
+	println!("  {:<30}{}", ($cmd).0, ($cmd).1)
     };
 }
 
-fn usage(program: String) {
-    println!("Usage: {} <command> [Options]", program);
-    // let print_cmd_usage = |(format, desc)| println!()
-    let cmd_usage = cmd_init::usage_message();
-    print_cmd_usage!(cmd_usage);
-    // println!(" {:>20}--  {}",  cmd_usage.0, cmd_usage.1);
-    let cmd_usage = cmd_build::usage_message();
-    print_cmd_usage!(cmd_usage);
-    
-    print_cmd_usage!(("help", "Display this help message"));
-    // println!(" help{<20}  Display this help message", "--");
+/// Every built-in command, registering one here is all it takes to add it
+fn commands() -> Registry {
+    let mut registry = Registry::new();
+    registry.register(cmd_init::InitCommand);
+    registry.register(cmd_build::BuildCommand);
+    registry.register(cmd_history::HistoryCommand);
+    registry.register(cmd_rollback::RollbackCommand);
+    registry.register(cmd_template::TemplateCommand);
+    registry.register(cmd_export::ExportCommand);
+    registry.register(cmd_import::ImportCommand);
+    registry.register(cmd_ide::IdeCommand);
+    registry.register(cmd_info::InfoCommand);
+    registry.register(cmd_completions::CompletionsCommand);
+    registry.register(cmd_man::ManCommand);
+    return registry;
+}
+
+fn usage(program: &str, registry: &Registry) {
+    println!("Usage: {} [+toolchain] [Options] <command> [args]", program);
+    registry.print_commands();
+    let plugins = plugins::list_plugins();
+    if !plugins.is_empty() {
+        println!("Plugins:");
+        for (name, path) in plugins.iter() {
+            print_cmd_usage!((name, path.display()));
+        }
+    }
+    println!("{}", global_spec().help());
+    println!("Run `{} help <command>` for the usage of a command", program);
+}
+
+fn help(ctx: &Context, program: &str, registry: &Registry, topic: Option<&String>) -> ExitCode {
+    let name = match topic {
+        None => {
+            usage(program, registry);
+            return ExitCode::SUCCESS;
+        },
+        Some(name) => name,
+    };
+    if let Some(cmd) = registry.find(name) {
+        registry.print_help(program, cmd);
+        return ExitCode::SUCCESS;
+    }
+    // Plugins document themselves
+    return match plugins::find_plugin(name) {
+        Some(plugin) => run_plugin(ctx, &plugin, &["--help".to_string()]),
+        None => registry.unknown_command(ctx, name),
+    };
 }
 
 fn main() -> ExitCode {
-    let cwd = match env::current_dir() {
+    let mut ctx = match Context::from_current_dir() {
         Ok(value) => value,
         Err(err) => {
-            error!("Spores failed to reach current directory: {}", err);
-            return ExitCode::FAILURE;
+            // No context to log through yet
+            eprintln!("[ERROR] Spores failed to reach current directory: {}", err);
+            return ExitCode::from(exit_code::IO_ERROR);
         },
     };
-    set_cwd(&cwd);
     let mut args:Vec<String> = env::args().collect();
     let program = args.remove(0);
+    let registry = commands();
+    // `kinoko +nightly build` like cargo, the toolchain always comes first
+    if let Some(toolchain) = args.first().and_then(|arg| arg.strip_prefix('+')).map(String::from) {
+        if toolchain.is_empty() {
+            error!(ctx, "Missing toolchain name after `+`");
+            return ExitCode::from(exit_code::USAGE);
+        }
+        ctx.toolchain = Some(toolchain);
+        args.remove(0);
+    }
+    let args = match args::parse(&global_spec(), args) {
+        Ok(parsed) => match apply_global_options(&mut ctx, &parsed) {
+            Ok(_) => parsed.rest,
+            Err(err) => {
+                error!(ctx, "{}", err);
+                return ExitCode::from(exit_code::USAGE);
+            },
+        },
+        Err(ArgError::HelpRequested) => {
+            usage(&program, &registry);
+            return ExitCode::SUCCESS;
+        },
+        Err(err) => {
+            error!(ctx, "{}", err);
+            return ExitCode::from(exit_code::USAGE);
+        },
+    };
     if args.is_empty() {
-	error!("No command was provided.");
-	usage(program);
-	return ExitCode::FAILURE;
+	error!(ctx, "No command was provided.");
+	usage(&program, &registry);
+	return ExitCode::from(exit_code::USAGE);
     }
-    if args[0] == String::from("help") {
-        usage(program);
-        return ExitCode::SUCCESS;
+    if args[0] == "help" {
+        return help(&ctx, &program, &registry, args.get(1));
     }
 
-    if cmd_init::check_args(&args) {
-        return match cmd_init::run_command(cwd, args) {
-            Ok(_) => ExitCode::SUCCESS,
-            Err(e) => {
-                error!("Failed to initialize project: {}", e);
-                ExitCode::FAILURE
-            },
-        }
-    }
-    if cmd_build::check_args(&args) {
-	return match cmd_build::run_command(cwd, args) {
-	    Ok(_) => ExitCode::SUCCESS,
-	    Err(e) => {
-		error!("Failed to build: {}", e);
-		ExitCode::FAILURE
-	    },
-	}
+    if let Some(code) = registry.dispatch(&ctx, &program, args.clone()) {
+        return code;
     }
+    return match plugins::find_plugin(&args[0]) {
+        Some(plugin) => run_plugin(&ctx, &plugin, &args[1..]),
+        None => registry.unknown_command(&ctx, &args[0]),
+    };
+}
+
+fn run_plugin(ctx: &Context, plugin: &Path, args: &[String]) -> ExitCode {
+    return match plugins::run_plugin(ctx, plugin, args) {
+        Ok(code) => code,
+        Err(e) => {
+            error!(ctx, "{}", e);
+            e.to_exit_code()
+        },
+    };
+}
 
-    error!("Unknown command passed by: {}", args[0]);
-    
-    
-    return ExitCode::FAILURE;
+/// Options in front of the command, the command's name and arguments are left in `rest`
+fn global_spec() -> ArgSpec {
+    let mut spec = ArgSpec::new();
+    spec.options = global_options();
+    spec.stop_at_positional = true;
+    return spec;
 }
 
 #[allow(unused)]
@@ -136,14 +222,16 @@ fn rust_file_dir_entry_checker(entry: &DirEntry) -> DirEntryAction {
 
 #[allow(unused)]
 fn search_directory_for_main_function<P: AsRef<Path> + Clone, C: Fn(&DirEntry) -> DirEntryAction>(
+    ctx: &Context,
     search_dir: P,
     dir_entry_checker: C,
 ) -> Result<PathBuf, MainFnSearchError> {
-    return search_directory_for_main_function_recursor(search_dir, Rc::new(dir_entry_checker), 0);
+    return search_directory_for_main_function_recursor(ctx, search_dir, Rc::new(dir_entry_checker), 0);
 }
 
 #[allow(unused)]
 fn search_directory_for_main_function_recursor<P: AsRef<Path> + Clone, C: Fn(&DirEntry) -> DirEntryAction>(
+    ctx: &Context,
     search_dir: P,
     dir_entry_checker: Rc<C>,
     recursion_level: usize
@@ -152,7 +240,7 @@ fn search_directory_for_main_function_recursor<P: AsRef<Path> + Clone, C: Fn(&Di
     for entry in entries {
         let entry = match entry {
             Err(e) => {
-                error!("Failed to read entry: {}", e);
+                warn!(ctx, "Failed to read entry: {}", e);
                 continue;
             },
             Ok(entry) => entry,
@@ -162,10 +250,10 @@ fn search_directory_for_main_function_recursor<P: AsRef<Path> + Clone, C: Fn(&Di
             DirEntryAction::Ignore => continue,
             DirEntryAction::ReadDir => {
                 if recursion_level > 3 {
-                    error!("Attempting too much recursion, skipping check of path: {}", entry_path.display());
+                    warn!(ctx, "Attempting too much recursion, skipping check of path: {}", entry_path.display());
                     continue;
                 }
-                let subdir_result = search_directory_for_main_function_recursor(entry_path.clone(), dir_entry_checker.clone(), recursion_level + 1);
+                let subdir_result = search_directory_for_main_function_recursor(ctx, entry_path.clone(), dir_entry_checker.clone(), recursion_level + 1);
                 if subdir_result.is_ok() {
                     return subdir_result;
                 }
@@ -173,7 +261,7 @@ fn search_directory_for_main_function_recursor<P: AsRef<Path> + Clone, C: Fn(&Di
             DirEntryAction::ReadFile => {
                 match fs::read_to_string(&entry_path) {
                     Err(err) => {
-                        error!("Failed to check file {}: {}", entry_path.display(), err);
+                        warn!(ctx, "Failed to check file {}: {}", entry_path.display(), err);
                         continue;
                     },
                     Ok(content) => {
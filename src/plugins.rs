@@ -0,0 +1,109 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+use kinoko::context::Context;
+use kinoko::data_structs::Kinoko;
+use kinoko::error::KinokoError;
+use kinoko::utility::debug;
+
+/// Executables named `kinoko-<name>` on the PATH are ran as `kinoko <name>`
+const PLUGIN_PREFIX: &'static str = "kinoko-";
+
+/// First `kinoko-<name>` executable on the PATH
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.starts_with('-') || name.contains('/') || name.contains('\\') {
+        return None;
+    }
+    let file_name = format!("{}{}{}", PLUGIN_PREFIX, name, env::consts::EXE_SUFFIX);
+    for dir in path_dirs() {
+        let candidate = dir.join(&file_name);
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+    }
+    return None;
+}
+
+/// Every plugin on the PATH as `(name, path)`, sorted by name. A name found twice keeps the first one like the shell would
+pub fn list_plugins() -> Vec<(String, PathBuf)> {
+    let mut plugins: Vec<(String, PathBuf)> = Vec::new();
+    for dir in path_dirs() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = match file_name.strip_prefix(PLUGIN_PREFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+            // Only `.exe` files are ran on windows
+            let name = match name.strip_suffix(env::consts::EXE_SUFFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+            if name.is_empty() || plugins.iter().any(|(known, _)| known == name) || !is_executable(&entry.path()) {
+                continue;
+            }
+            plugins.push((name.to_string(), entry.path()));
+        }
+    }
+    plugins.sort_by(|(left, _), (right, _)| left.cmp(right));
+    return plugins;
+}
+
+/// Runs a plugin with the project's paths in its environment, its exit code becomes kinoko's
+pub fn run_plugin(ctx: &Context, plugin: &Path, args: &[String]) -> Result<ExitCode, KinokoError> {
+    let mut cmd = Command::new(plugin);
+    cmd.args(args).current_dir(&ctx.cwd);
+    if let Ok(exe) = env::current_exe() {
+        cmd.env("KINOKO", exe);
+    }
+    cmd.env("KINOKO_PROJECT_DIR", &ctx.cwd);
+    if let Some(toolchain) = &ctx.toolchain {
+        cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    let kin = Kinoko::at(ctx.cwd.clone()).context(ctx.clone()).build();
+    if kin.has_roots_at_cwd() {
+        cmd.env("KINOKO_MUSHROOM", kin.get_mushroom_path());
+        if let Ok(mushroom) = kin.pick_mushroom() {
+            cmd.env("KINOKO_HEAD", kin.get_mushroom_head_path(&mushroom));
+        }
+    }
+    debug!(ctx, "{:?}", cmd);
+
+    let status = match cmd.status() {
+        Ok(status) => status,
+        Err(err) => return Err(KinokoError::io(format!("Failed to run plugin {}", plugin.display()), err)),
+    };
+    return Ok(match status.code() {
+        // Codes that don't fit an exit code (Windows allows any u32) still mean failure
+        Some(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        None => ExitCode::FAILURE,
+    });
+}
+
+fn path_dirs() -> Vec<PathBuf> {
+    return match env::var_os("PATH") {
+        Some(path) => env::split_paths(&path).collect(),
+        None => Vec::new(),
+    };
+}
+
+#[cfg(target_family="unix")]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    return match fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    };
+}
+
+#[cfg(not(target_family="unix"))]
+fn is_executable(path: &Path) -> bool {
+    return path.is_file();
+}
@@ -0,0 +1,123 @@
+//! `rust-project.json` describing a mushroom to rust-analyzer, which only understands cargo on its own
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::fs;
+use std::io;
+
+use data_structs::{Kinoko, Mushroom};
+use utility::{debug, command_line};
+use toolchain;
+use build_script;
+
+pub const RUST_PROJECT_FILE: &'static str = "rust-project.json";
+
+pub fn rust_project_path(kinoko: &Kinoko) -> PathBuf {
+    return kinoko.cwd.join(RUST_PROJECT_FILE);
+}
+
+/// A rust-project.json that exists but is older than what it's made of: the kinoko.🍄, the rust-toolchain file
+/// deciding the sysroot or the last build script output. Missing ones are never stale
+pub fn is_stale(kinoko: &Kinoko) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let project = match modified(&rust_project_path(kinoko)) {
+        Some(project) => project,
+        None => return false,
+    };
+    let mut inputs = vec![kinoko.get_mushroom_path()];
+    inputs.extend(toolchain::find_toolchain_file(kinoko));
+    if let Ok(mushroom) = kinoko.pick_mushroom() {
+        inputs.push(build_script::output_path(kinoko, &mushroom));
+    }
+    return inputs.iter().any(|input| modified(input).map(|input| input > project).unwrap_or(false));
+}
+
+/// Writes the rust-project.json of the mushroom next to it
+pub fn write_rust_project(kinoko: &Kinoko, mushroom: &Mushroom) -> io::Result<PathBuf> {
+    let path = rust_project_path(kinoko);
+    fs::write(&path, rust_project_json(kinoko, mushroom, find_sysroot(kinoko, mushroom).as_deref()))?;
+    return Ok(path);
+}
+
+/// Sysroot of the rustc germinating the mushroom, where rust-analyzer finds the standard library
+pub fn find_sysroot(kinoko: &Kinoko, mushroom: &Mushroom) -> Option<String> {
+    let mut cmd = Command::new(toolchain::resolve_rustc(kinoko, mushroom).ok()?);
+    cmd.arg("--print").arg("sysroot").current_dir(&kinoko.cwd);
+    debug!(&kinoko.ctx, "{}", command_line(&cmd));
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    return if sysroot.is_empty() { None } else { Some(sysroot) };
+}
+
+/// The library comes first so the head can depend on it by index
+pub fn rust_project_json(kinoko: &Kinoko, mushroom: &Mushroom, sysroot: Option<&str>) -> String {
+    let edition = mushroom.edition.clone().unwrap_or(String::from("2015"));
+    let mut cfg: Vec<String> = mushroom.features.iter()
+        .map(|feature| json_string(&format!("feature=\"{}\"", feature)))
+        .collect();
+    let mut env: Vec<String> = mushroom.package_env().iter()
+        .map(|(name, value)| format!("{}: {}", json_string(name), json_string(value)))
+        .collect();
+    // What the build script asked for the last time it ran
+    if let Some(build) = build_script::cached_output(kinoko, mushroom) {
+        cfg.extend(build.cfgs.iter().map(|build_cfg| json_string(build_cfg)));
+        env.push(format!("{}: {}", json_string("OUT_DIR"), json_string(&build_script::out_dir(kinoko, mushroom).to_string_lossy())));
+        env.extend(build.envs.iter().map(|(name, value)| format!("{}: {}", json_string(name), json_string(value))));
+    }
+    let head_name = Path::new(&mushroom.head).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(String::from("main"));
+
+    let mut crates = Vec::new();
+    let mut head_deps = String::new();
+    if let (Some(lib), Some(lib_name)) = (&mushroom.lib, mushroom.get_lib_name()) {
+        crates.push(crate_json(&lib_name, &kinoko.cwd.join(lib), &edition, "", &cfg, &env));
+        head_deps = format!("{{ \"crate\": 0, \"name\": {} }}", json_string(&lib_name));
+    }
+    crates.push(crate_json(&head_name, &kinoko.cwd.join(&mushroom.root), &edition, &head_deps, &cfg, &env));
+
+    let mut json = String::from("{\n");
+    if let Some(sysroot) = sysroot {
+        json.push_str(&format!("  \"sysroot\": {},\n", json_string(sysroot)));
+        let sysroot_src = Path::new(sysroot).join("lib").join("rustlib").join("src").join("rust").join("library");
+        if sysroot_src.is_dir() {
+            json.push_str(&format!("  \"sysroot_src\": {},\n", json_string(&sysroot_src.to_string_lossy())));
+        }
+    }
+    json.push_str(&format!("  \"crates\": [\n{}\n  ]\n}}\n", crates.join(",\n")));
+    return json;
+}
+
+fn crate_json(name: &str, root: &Path, edition: &str, deps: &str, cfg: &Vec<String>, env: &Vec<String>) -> String {
+    let mut json = String::from("    {\n");
+    json.push_str(&format!("      \"display_name\": {},\n", json_string(name)));
+    json.push_str(&format!("      \"root_module\": {},\n", json_string(&root.to_string_lossy())));
+    json.push_str(&format!("      \"edition\": {},\n", json_string(edition)));
+    json.push_str(&format!("      \"deps\": [{}],\n", deps));
+    json.push_str(&format!("      \"cfg\": [{}],\n", cfg.join(", ")));
+    if !env.is_empty() {
+        json.push_str(&format!("      \"env\": {{ {} }},\n", env.join(", ")));
+    }
+    json.push_str("      \"is_workspace_member\": true\n");
+    json.push_str("    }");
+    return json;
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    return json;
+}
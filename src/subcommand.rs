@@ -0,0 +1,208 @@
+use std::process::ExitCode;
+
+use kinoko::context::{Context, Verbosity, ColorChoice};
+use kinoko::error::{KinokoError, exit_code};
+use kinoko::utility::error;
+use args::{self, ArgSpec, ArgError, Completion, Opt, ParsedArgs};
+
+pub type CmdResult = Result<(), KinokoError>;
+
+impl From<ArgError> for KinokoError {
+    fn from(err: ArgError) -> KinokoError {
+        return KinokoError::Args(err.to_string());
+    }
+}
+
+/// A command of the CLI, `kinoko <name> [args]`
+pub trait Subcommand {
+    fn name(&self) -> &'static str;
+
+    /// Other names the command answers to
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Options and arguments that go after the name, the usage and `--help` are generated from it
+    fn args(&self) -> ArgSpec;
+
+    /// One line description for the command list
+    fn summary(&self) -> &'static str;
+
+    /// Everything else worth knowing, shown by `kinoko help <name>`
+    fn help(&self) -> &'static str;
+
+    /// Command lines with what they do, shown by `kinoko help <name>` and the man pages
+    fn examples(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// Prefix of the error shown when the command fails
+    fn failure_message(&self) -> &'static str;
+
+    fn run(&self, ctx: &Context, args: ParsedArgs) -> CmdResult;
+}
+
+/// Every command the CLI knows of, in the order they're listed
+pub struct Registry {
+    commands: Vec<Box<dyn Subcommand>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        return Registry { commands: Vec::new() };
+    }
+
+    pub fn register<C: Subcommand + 'static>(&mut self, command: C) {
+        self.commands.push(Box::new(command));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Subcommand> {
+        return self.commands.iter().map(|cmd| cmd.as_ref());
+    }
+
+    pub fn find(&self, name: &str) -> Option<&dyn Subcommand> {
+        return self.commands.iter()
+            .find(|cmd| cmd.name() == name || cmd.aliases().contains(&name))
+            .map(|cmd| cmd.as_ref());
+    }
+
+    /// Closest command name to a mistyped one, if any is close enough
+    pub fn suggest(&self, name: &str) -> Option<&'static str> {
+        let mut best: Option<(usize, &'static str)> = None;
+        for cmd in self.commands.iter() {
+            for candidate in [cmd.name()].iter().chain(cmd.aliases().iter()) {
+                let distance = edit_distance(name, candidate);
+                if best.map_or(true, |(best_distance, _)| distance < best_distance) {
+                    best = Some((distance, cmd.name()));
+                }
+            }
+        }
+        let max_distance = if name.chars().count() > 4 { 2 } else { 1 };
+        return match best {
+            Some((distance, name)) if distance <= max_distance => Some(name),
+            _ => None,
+        };
+    }
+
+    /// Command list for the general usage
+    pub fn print_commands(&self) {
+        let width = self.commands.iter().map(|cmd| cmd.name().len()).max().unwrap_or(0).max("help".len());
+        println!("Commands:");
+        for cmd in self.commands.iter() {
+            println!("  {:<width$}  {}", cmd.name(), cmd.summary(), width = width);
+        }
+        println!("  {:<width$}  {}", "help", "Display this help message, or a command's with `help <command>`", width = width);
+    }
+
+    pub fn print_help(&self, program: &str, cmd: &dyn Subcommand) {
+        println!("{}", format!("Usage: {} {} {}", program, cmd.name(), cmd.args().usage()).trim_end());
+        if !cmd.aliases().is_empty() {
+            println!("Aliases: {}", cmd.aliases().join(", "));
+        }
+        println!();
+        println!("{}", cmd.summary());
+        if !cmd.help().is_empty() {
+            println!();
+            println!("{}", cmd.help());
+        }
+        println!();
+        println!("{}", command_spec(cmd).help());
+        if !cmd.examples().is_empty() {
+            println!();
+            println!("Examples:");
+            for (example, description) in cmd.examples().iter() {
+                println!("  {}", example);
+                println!("      {}", description);
+            }
+        }
+    }
+
+    /// Parses the arguments of the command named by the first one and runs it, `None` when there's no such command
+    pub fn dispatch(&self, ctx: &Context, program: &str, mut args: Vec<String>) -> Option<ExitCode> {
+        let cmd = self.find(&args[0])?;
+        args.remove(0);
+        let result = match args::parse(&command_spec(cmd), args) {
+            Err(ArgError::HelpRequested) => {
+                self.print_help(program, cmd);
+                return Some(ExitCode::SUCCESS);
+            },
+            Err(err) => Err(KinokoError::from(err)),
+            Ok(parsed) => {
+                // Options after the command's name only apply to it
+                let mut ctx = ctx.clone();
+                match apply_global_options(&mut ctx, &parsed) {
+                    Ok(_) => cmd.run(&ctx, parsed),
+                    Err(err) => Err(KinokoError::from(err)),
+                }
+            },
+        };
+        return Some(match result {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(e) => {
+                error!(ctx, "{}: {}", cmd.failure_message(), e);
+                e.to_exit_code()
+            },
+        });
+    }
+
+    /// Reports a command that isn't registered
+    pub fn unknown_command(&self, ctx: &Context, name: &str) -> ExitCode {
+        match self.suggest(name) {
+            Some(suggestion) => error!(ctx, "Unknown command passed by: {}, did you mean `{}`?", name, suggestion),
+            None => error!(ctx, "Unknown command passed by: {}", name),
+        }
+        return ExitCode::from(exit_code::USAGE);
+    }
+}
+
+/// Options every command accepts besides its own
+pub fn global_options() -> Vec<Opt> {
+    return vec![
+        // Caught by the parser before any other option, listed so it shows in help pages and completions
+        Opt::flag("help", Some('h'), "Display the help of kinoko or of the command"),
+        Opt::flag("quiet", Some('q'), "Only print errors"),
+        Opt::flag("verbose", Some('v'), "Print more details, `-vv` also prints the commands ran"),
+        Opt::value("color", None, "auto|always|never", "Color kinoko's messages, `auto` respects NO_COLOR and colors terminals only")
+            .complete(Completion::Words(&["auto", "always", "never"])),
+    ];
+}
+
+pub fn apply_global_options(ctx: &mut Context, parsed: &ParsedArgs) -> Result<(), ArgError> {
+    if parsed.flag("quiet") {
+        ctx.verbosity = Verbosity::Quiet;
+    }
+    ctx.verbosity = match parsed.count("verbose") {
+        0 => ctx.verbosity,
+        1 => Verbosity::Verbose,
+        _ => Verbosity::Debug,
+    };
+    if let Some(color) = parsed.value("color") {
+        ctx.color = match ColorChoice::parse(color) {
+            Some(color) => color,
+            None => return Err(ArgError::InvalidValue("--color".to_string(), color.to_string())),
+        };
+    }
+    return Ok(());
+}
+
+/// The command's own arguments followed by the global options
+pub fn command_spec(cmd: &dyn Subcommand) -> ArgSpec {
+    let mut spec = cmd.args();
+    spec.options.extend(global_options());
+    return spec;
+}
+
+/// Levenshtein distance between two words
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    for (i, l) in left.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, r) in right.iter().enumerate() {
+            let substitution = previous[j] + if l == *r { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    return previous[right.len()];
+}
@@ -0,0 +1,411 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+
+use kinoko::utility::{config_dir, warn};
+use kinoko::error::KinokoError;
+use kinoko::context::Context;
+
+pub const DEFAULT_TEMPLATE: &'static str = "hello";
+/// Folders deeper than this are left out of a template
+const MAX_TEMPLATE_DEPTH: usize = 8;
+const BUILTIN_TEMPLATES: [(&'static str, &'static str); 5] = [
+    ("hello", "Hello world that prints the arguments it was given"),
+    ("cli", "Command line tool with flag parsing and a usage message"),
+    ("lib", "Library module with tests plus a small binary using it"),
+    ("stdin-filter", "Reads stdin line by line and writes the result to stdout"),
+    ("multi-module", "Binary split into a few modules"),
+];
+
+pub struct TemplateFile {
+    /// Path relative to the project directory
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+}
+
+pub struct Template {
+    pub name: String,
+    pub files: Vec<TemplateFile>,
+}
+impl Template {
+    /// Looks for a user template first so built-in ones can be overridden
+    pub fn find(ctx: &Context, name: &str) -> Result<Template, KinokoError> {
+        if !is_valid_name(name) {
+            return Err(KinokoError::Init(format!("Invalid template name: {}", name)));
+        }
+        if let Some(dir) = user_templates_dir() {
+            let template_dir = dir.join(name);
+            if template_dir.is_dir() {
+                return Template::load(ctx, name, &template_dir, &[]);
+            }
+        }
+        return match builtin_files(name) {
+            Some(files) => Ok(Template {
+                name: name.to_string(),
+                files: files.into_iter().map(|(path, contents)| TemplateFile {
+                    path: PathBuf::from(path),
+                    contents: contents.as_bytes().to_vec(),
+                }).collect(),
+            }),
+            None => Err(KinokoError::Init(format!("Unknown template `{}`, see `kinoko template list` for the available ones", name))),
+        };
+    }
+
+    /// Reads every file under `dir` except the `skipped` folders, relative to `dir`
+    pub fn load(ctx: &Context, name: &str, dir: &Path, skipped: &[PathBuf]) -> Result<Template, KinokoError> {
+        let mut paths = Vec::new();
+        let mut too_deep = Vec::new();
+        if let Err(err) = collect_files(dir, dir, skipped, &mut paths, &mut too_deep, 0) {
+            return Err(KinokoError::io_at("read template", &dir.to_path_buf(), err));
+        }
+        for folder in too_deep.iter() {
+            warn!(ctx, "Left {} out of template `{}`, it's more than {} folders deep", folder.display(), name, MAX_TEMPLATE_DEPTH);
+        }
+        paths.sort();
+        let mut files = Vec::new();
+        for path in paths.into_iter() {
+            match fs::read(dir.join(&path)) {
+                Ok(contents) => files.push(TemplateFile { path: path, contents: contents }),
+                Err(err) => return Err(KinokoError::io_at("read template file", &dir.join(&path), err)),
+            }
+        }
+        return Ok(Template { name: name.to_string(), files: files });
+    }
+
+    pub fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<&TemplateFile> {
+        return self.files.iter().find(|file| file.path == path.as_ref());
+    }
+
+    /// Entry file of the template, `src/main.rs` unless another file holds the main function
+    pub fn root(&self) -> String {
+        let default_root = Path::new("src").join("main.rs");
+        if self.get_file(&default_root).is_some() {
+            return String::from("src/main.rs");
+        }
+        for file in self.files.iter() {
+            if file.path.extension().map(|ext| ext != "rs").unwrap_or(true) {
+                continue;
+            }
+            if String::from_utf8_lossy(&file.contents).contains("fn main(") {
+                return path_to_mushroom_string(&file.path);
+            }
+        }
+        return String::from("src/main.rs");
+    }
+
+    /// Substitutes `{{key}}` placeholders in file names and contents
+    pub fn render(&self, vars: &[(&str, &str)]) -> Vec<TemplateFile> {
+        return self.files.iter().map(|file| {
+            let path = render_text(&path_to_mushroom_string(&file.path), vars);
+            let contents = match String::from_utf8(file.contents.clone()) {
+                Ok(text) => render_text(&text, vars).into_bytes(),
+                // Binary files are copied as they are
+                Err(_) => file.contents.clone(),
+            };
+            TemplateFile { path: PathBuf::from(path), contents: contents }
+        }).collect();
+    }
+}
+
+pub fn render_text(text: &str, vars: &[(&str, &str)]) -> String {
+    let mut result = text.to_string();
+    for (key, value) in vars.iter() {
+        result = result.replace(&format!("{{{{{}}}}}", key), value);
+    }
+    return result;
+}
+
+/// A single folder name, so it can't reach outside of the templates folder
+pub fn is_valid_name(name: &str) -> bool {
+    return !name.is_empty() && !name.contains('/') && !name.contains('\\') && !name.starts_with('.');
+}
+
+pub fn user_templates_dir() -> Option<PathBuf> {
+    return config_dir().map(|dir| dir.join("templates"));
+}
+
+/// Built-in templates followed by the user's own, as `(name, description)`
+pub fn list_templates() -> Vec<(String, String)> {
+    let mut templates: Vec<(String, String)> = BUILTIN_TEMPLATES.iter()
+        .map(|(name, desc)| (name.to_string(), desc.to_string()))
+        .collect();
+    let dir = match user_templates_dir() {
+        Some(dir) => dir,
+        None => return templates,
+    };
+    let mut user_templates = Vec::new();
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                user_templates.push((entry.file_name().to_string_lossy().to_string(), format!("User template at {}", entry.path().display())));
+            }
+        }
+    }
+    user_templates.sort();
+    templates.extend(user_templates);
+    return templates;
+}
+
+fn path_to_mushroom_string(path: &Path) -> String {
+    let parts: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    return parts.join("/");
+}
+
+fn collect_files(base: &Path, dir: &Path, skipped: &[PathBuf], files: &mut Vec<PathBuf>, too_deep: &mut Vec<PathBuf>, recursion_level: usize) -> std::io::Result<()> {
+    if recursion_level > MAX_TEMPLATE_DEPTH {
+        too_deep.push(dir.strip_prefix(base).unwrap_or(dir).to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = match path.strip_prefix(base) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => continue,
+        };
+        if skipped.iter().any(|skip| relative == *skip) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(base, &path, skipped, files, too_deep, recursion_level + 1)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    return Ok(());
+}
+
+fn builtin_files(name: &str) -> Option<Vec<(&'static str, &'static str)>> {
+    return match name {
+        "hello" => Some(vec![("src/main.rs", HELLO_MAIN)]),
+        "cli" => Some(vec![("src/main.rs", CLI_MAIN)]),
+        "lib" => Some(vec![("src/main.rs", LIB_MAIN), ("src/lib.rs", LIB_LIB)]),
+        "stdin-filter" => Some(vec![("src/main.rs", STDIN_FILTER_MAIN)]),
+        "multi-module" => Some(vec![
+            ("src/main.rs", MULTI_MODULE_MAIN),
+            ("src/config.rs", MULTI_MODULE_CONFIG),
+            ("src/commands.rs", MULTI_MODULE_COMMANDS),
+        ]),
+        _ => None,
+    };
+}
+
+const HELLO_MAIN: &'static str = r##"use std::process::ExitCode;
+
+fn run(program: &str, args: Vec<String>) -> Result<(), String> {
+    println!("Hello world!");
+    print!("] {}", program);
+    for arg in args.iter() {
+        print!(" {:?}", arg);
+    }
+    println!();
+    println!("Arguments count: {}", args.len());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut args:Vec<String> = std::env::args().collect();
+    let program:String = args.remove(0);
+    match run(&program, args) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+"##;
+
+const CLI_MAIN: &'static str = r##"use std::process::ExitCode;
+
+struct Options {
+    verbose: bool,
+    inputs: Vec<String>,
+}
+
+fn usage(program: &str) {
+    println!("Usage: {} [options] <input>...", program);
+    println!("  -h, --help       Display this help message");
+    println!("  -v, --verbose    Print more information while running");
+}
+
+fn parse_args(args: Vec<String>) -> Result<Option<Options>, String> {
+    let mut options = Options { verbose: false, inputs: Vec::new() };
+    let mut only_inputs = false;
+    for arg in args.into_iter() {
+        if only_inputs {
+            options.inputs.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-v" | "--verbose" => options.verbose = true,
+            "--" => only_inputs = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => options.inputs.push(arg),
+        }
+    }
+    if options.inputs.is_empty() {
+        return Err("No input was provided".to_string());
+    }
+    Ok(Some(options))
+}
+
+fn run(options: Options) -> Result<(), String> {
+    for input in options.inputs.iter() {
+        if options.verbose {
+            println!("[{{project_name}}] Processing {}", input);
+        }
+        println!("{}", input);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut args:Vec<String> = std::env::args().collect();
+    let program:String = args.remove(0);
+    let options = match parse_args(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            usage(&program);
+            return ExitCode::SUCCESS;
+        },
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            usage(&program);
+            return ExitCode::FAILURE;
+        },
+    };
+    match run(options) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+"##;
+
+const LIB_MAIN: &'static str = r##"use std::process::ExitCode;
+
+#[path = "lib.rs"]
+mod library;
+
+fn main() -> ExitCode {
+    let args:Vec<String> = std::env::args().skip(1).collect();
+    let numbers: Result<Vec<i64>, _> = args.iter().map(|arg| arg.parse::<i64>()).collect();
+    match numbers {
+        Ok(numbers) => {
+            println!("Sum: {}", library::sum(&numbers));
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+"##;
+
+const LIB_LIB: &'static str = r##"//! Library code of {{project_name}}
+
+pub fn sum(numbers: &[i64]) -> i64 {
+    numbers.iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_numbers() {
+        assert_eq!(sum(&[1, 2, 3]), 6);
+        assert_eq!(sum(&[]), 0);
+    }
+}
+"##;
+
+const STDIN_FILTER_MAIN: &'static str = r##"use std::io::{self, BufRead, BufWriter, Write};
+use std::process::ExitCode;
+
+fn filter(line: &str) -> Option<String> {
+    let line = line.trim_end();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.to_string())
+}
+
+fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for line in stdin.lock().lines() {
+        if let Some(filtered) = filter(&line?) {
+            writeln!(out, "{}", filtered)?;
+        }
+    }
+    out.flush()
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(_) => ExitCode::SUCCESS,
+        // Reader went away, not an error for a filter
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+"##;
+
+const MULTI_MODULE_MAIN: &'static str = r##"use std::process::ExitCode;
+
+mod config;
+mod commands;
+
+fn main() -> ExitCode {
+    let args:Vec<String> = std::env::args().skip(1).collect();
+    let config = config::Config::from_args(&args);
+    match commands::run(&config) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+"##;
+
+const MULTI_MODULE_CONFIG: &'static str = r##"pub struct Config {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl Config {
+    pub fn from_args(args: &[String]) -> Config {
+        let command = args.get(0).cloned().unwrap_or_else(|| "hello".to_string());
+        Config {
+            command: command,
+            args: args.iter().skip(1).cloned().collect(),
+        }
+    }
+}
+"##;
+
+const MULTI_MODULE_COMMANDS: &'static str = r##"use config::Config;
+
+pub fn run(config: &Config) -> Result<(), String> {
+    match config.command.as_str() {
+        "hello" => {
+            println!("Hello from {{project_name}}!");
+            Ok(())
+        },
+        "echo" => {
+            println!("{}", config.args.join(" "));
+            Ok(())
+        },
+        other => Err(format!("Unknown command: {}", other)),
+    }
+}
+"##;
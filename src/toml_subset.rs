@@ -0,0 +1,385 @@
+//! Just enough TOML to read a Cargo.toml without pulling in a dependency.
+//! Supports tables, arrays of tables, dotted keys, strings, integers, floats,
+//! booleans, arrays and inline tables. Dates and multi-line strings are not supported
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<TomlValue>),
+    Table(Vec<(String, TomlValue)>),
+}
+impl TomlValue {
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            TomlValue::String(s) => Some(s),
+            _ => None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<TomlValue>> {
+        return match self {
+            TomlValue::Array(values) => Some(values),
+            _ => None,
+        };
+    }
+}
+impl std::fmt::Display for TomlValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            TomlValue::String(s) => write!(f, "{:?}", s),
+            TomlValue::Integer(i) => write!(f, "{}", i),
+            TomlValue::Float(x) => write!(f, "{}", x),
+            TomlValue::Boolean(b) => write!(f, "{}", b),
+            TomlValue::Array(values) => {
+                let parts: Vec<String> = values.iter().map(|v| format!("{}", v)).collect();
+                write!(f, "[{}]", parts.join(", "))
+            },
+            TomlValue::Table(entries) => {
+                let parts: Vec<String> = entries.iter().map(|(k, v)| format!("{} = {}", k, v)).collect();
+                write!(f, "{{ {} }}", parts.join(", "))
+            },
+        };
+    }
+}
+
+/// A `[header]` or `[[header]]` section with the keys found under it, in order
+#[derive(Debug)]
+pub struct TomlTable {
+    pub header: String,
+    /// Segments of the header, quoted ones may hold dots: `target.'cfg(unix)'.dependencies` has three
+    pub path: Vec<String>,
+    pub is_array: bool,
+    pub entries: Vec<(String, TomlValue)>,
+}
+impl TomlTable {
+    pub fn get(&self, key: &str) -> Option<&TomlValue> {
+        return self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+    }
+}
+
+#[derive(Debug)]
+pub struct TomlDocument {
+    /// Keys before the first header are kept in a table with an empty header
+    pub tables: Vec<TomlTable>,
+}
+impl TomlDocument {
+    pub fn table(&self, header: &str) -> Option<&TomlTable> {
+        return self.tables.iter().find(|t| t.header == header && !t.is_array);
+    }
+
+    pub fn array_tables<'a>(&'a self, header: &'a str) -> impl Iterator<Item = &'a TomlTable> + 'a {
+        return self.tables.iter().filter(move |t| t.header == header && t.is_array);
+    }
+
+    pub fn get(&self, header: &str, key: &str) -> Option<&TomlValue> {
+        return self.table(header).and_then(|t| t.get(key));
+    }
+}
+
+pub fn parse(source: &str) -> Result<TomlDocument, String> {
+    let mut doc = TomlDocument { tables: vec![TomlTable { header: String::new(), path: Vec::new(), is_array: false, entries: Vec::new() }] };
+    let mut parser = Parser { chars: source.chars().collect(), pos: 0, line: 1 };
+    loop {
+        parser.skip_whitespace_and_comments(true);
+        let c = match parser.peek() {
+            Some(c) => c,
+            None => break,
+        };
+        if c == '[' {
+            parser.pos += 1;
+            let is_array = parser.peek() == Some('[');
+            if is_array {
+                parser.pos += 1;
+            }
+            let path = parser.parse_key()?;
+            parser.skip_whitespace_and_comments(false);
+            parser.expect(']')?;
+            if is_array {
+                parser.expect(']')?;
+            }
+            doc.tables.push(TomlTable { header: path.join("."), path: path, is_array: is_array, entries: Vec::new() });
+        } else {
+            let key = parser.parse_key()?;
+            parser.skip_whitespace_and_comments(false);
+            parser.expect('=')?;
+            parser.skip_whitespace_and_comments(false);
+            let value = parser.parse_value()?;
+            let table = doc.tables.last_mut().unwrap();
+            // Dotted keys are kept flat, `a.b = 1` is stored under the key `a.b`
+            table.entries.push((key.join("."), value));
+        }
+        parser.skip_whitespace_and_comments(false);
+        match parser.peek() {
+            None | Some('\n') => {},
+            Some(c) => return Err(parser.error(&format!("unexpected `{}` after value", c))),
+        }
+    }
+    return Ok(doc);
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.pos).cloned();
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        return Some(c);
+    }
+
+    fn error(&self, msg: &str) -> String {
+        return format!("line {}: {}", self.line, msg);
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        return match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(&format!("expected `{}` but found `{}`", expected, c))),
+            None => Err(self.error(&format!("expected `{}` but the file ended", expected))),
+        };
+    }
+
+    fn skip_whitespace_and_comments(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while let Some(c) = self.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else if c == ' ' || c == '\t' || c == '\r' || (newlines && c == '\n') {
+                self.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(false);
+            let part = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while let Some(c) = self.peek() {
+                        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                },
+            };
+            parts.push(part);
+            self.skip_whitespace_and_comments(false);
+            if self.peek() == Some('.') {
+                self.pos += 1;
+            } else {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<TomlValue, String> {
+        return match self.peek() {
+            Some('"') => {
+                if self.chars[self.pos..].starts_with(&['"', '"', '"']) {
+                    return Err(self.error("multi-line strings are not supported"));
+                }
+                Ok(TomlValue::String(self.parse_basic_string()?))
+            },
+            Some('\'') => Ok(TomlValue::String(self.parse_literal_string()?)),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_inline_table(),
+            Some(_) => self.parse_bare_value(),
+            None => Err(self.error("expected a value but the file ended")),
+        };
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('u') => self.parse_unicode_escape(4)?,
+                        Some('U') => self.parse_unicode_escape(8)?,
+                        Some(c) => return Err(self.error(&format!("unknown escape `\\{}`", c))),
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    value.push(escaped);
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self, len: usize) -> Result<char, String> {
+        let mut hex = String::new();
+        for _ in 0..len {
+            match self.next() {
+                Some(c) => hex.push(c),
+                None => return Err(self.error("unterminated unicode escape")),
+            }
+        }
+        return u32::from_str_radix(&hex, 16).ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| self.error(&format!("invalid unicode escape `{}`", hex)));
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<TomlValue, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(true);
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(TomlValue::Array(values));
+            }
+            values.push(self.parse_value()?);
+            self.skip_whitespace_and_comments(true);
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(TomlValue::Array(values)),
+                _ => return Err(self.error("expected `,` or `]` in array")),
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> Result<TomlValue, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(false);
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(TomlValue::Table(entries));
+            }
+            let key = self.parse_key()?.join(".");
+            self.skip_whitespace_and_comments(false);
+            self.expect('=')?;
+            self.skip_whitespace_and_comments(false);
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace_and_comments(false);
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(TomlValue::Table(entries)),
+                _ => return Err(self.error("expected `,` or `}` in inline table")),
+            }
+        }
+    }
+
+    fn parse_bare_value(&mut self) -> Result<TomlValue, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '_' || c == '.' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        if word == "true" {
+            return Ok(TomlValue::Boolean(true));
+        }
+        if word == "false" {
+            return Ok(TomlValue::Boolean(false));
+        }
+        let number = word.replace('_', "");
+        if let Ok(i) = number.parse::<i64>() {
+            return Ok(TomlValue::Integer(i));
+        }
+        if let Ok(x) = number.parse::<f64>() {
+            return Ok(TomlValue::Float(x));
+        }
+        return Err(self.error(&format!("unsupported value `{}`", word)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tables_and_values() {
+        let doc = parse("name = \"top\"\n[package]\nname = 'x' # comment\nversion = \"1.0\"\nnumbers = [1, 2_000, -3]\nratio = 0.5\npublish = false\n").unwrap();
+        assert_eq!(doc.get("", "name"), Some(&TomlValue::String("top".to_string())));
+        assert_eq!(doc.get("package", "name").and_then(|v| v.as_str()), Some("x"));
+        assert_eq!(doc.get("package", "numbers"), Some(&TomlValue::Array(vec![TomlValue::Integer(1), TomlValue::Integer(2000), TomlValue::Integer(-3)])));
+        assert_eq!(doc.get("package", "ratio"), Some(&TomlValue::Float(0.5)));
+        assert_eq!(doc.get("package", "publish"), Some(&TomlValue::Boolean(false)));
+    }
+
+    #[test]
+    fn keeps_quoted_header_segments() {
+        let doc = parse("[target.'cfg(target_os = \"a.b\")'.dependencies]\n").unwrap();
+        let table = doc.tables.last().unwrap();
+        assert_eq!(table.path, vec!["target", "cfg(target_os = \"a.b\")", "dependencies"]);
+    }
+
+    #[test]
+    fn arrays_of_tables_and_inline_tables() {
+        let doc = parse("[[bin]]\nname = \"a\"\n[[bin]]\nname = \"b\"\nopts = { path = \"src/b.rs\", test = true }\n").unwrap();
+        let names: Vec<&str> = doc.array_tables("bin").filter_map(|t| t.get("name").and_then(|v| v.as_str())).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(doc.table("bin").map(|t| t.header.clone()), None);
+        let opts = doc.array_tables("bin").last().unwrap().get("opts").unwrap();
+        assert_eq!(format!("{}", opts), "{ path = \"src/b.rs\", test = true }");
+    }
+
+    #[test]
+    fn dotted_keys_stay_flat() {
+        let doc = parse("dependencies.libc.version = \"0.2\"\n").unwrap();
+        assert!(doc.get("", "dependencies.libc.version").is_some());
+    }
+
+    #[test]
+    fn escapes_and_errors() {
+        let doc = parse("s = \"a\\tb\\u00e9\"\n").unwrap();
+        assert_eq!(doc.get("", "s").and_then(|v| v.as_str()), Some("a\tbé"));
+        assert!(parse("s = \"\"\"multi\"\"\"\n").is_err());
+        assert!(parse("s = \"open\n").unwrap_err().contains("unterminated string"));
+        assert!(parse("a = 1 2\n").is_err());
+        assert!(parse("d = 1979-05-27\n").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Picks the rustc a mushroom germinates with. In order: `kinoko +<toolchain>`, the `RUSTC` environment variable,
+//! the mushroom's `rustc:` key, the project's rust-toolchain file and finally whatever `rustc` is in the PATH
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use data_structs::{Kinoko, Mushroom, GerminationError};
+use utility::{warn, verbose, debug, command_line};
+
+/// Files rustup reads the toolchain of a project from
+pub const TOOLCHAIN_FILES: [&'static str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
+/// The rustc to germinate with, checked to exist so a wrong path fails before any hook or build script runs
+pub fn resolve_rustc(kinoko: &Kinoko, mushroom: &Mushroom) -> Result<OsString, GerminationError> {
+    if let Some(toolchain) = &kinoko.ctx.toolchain {
+        verbose!(&kinoko.ctx, "Toolchain = +{}", toolchain);
+        return match rustup_which_rustc(kinoko, Some(toolchain)) {
+            Ok(rustc) => existing_rustc(rustc, &format!("toolchain +{}", toolchain)),
+            Err(reason) => Err(GerminationError::ToolchainUnavailable(toolchain.clone(), reason)),
+        };
+    }
+    if let Some(rustc) = env::var_os("RUSTC").filter(|rustc| !rustc.is_empty()) {
+        verbose!(&kinoko.ctx, "RUSTC = {}", rustc.to_string_lossy());
+        return existing_rustc(program_at(&kinoko.ctx.cwd, rustc), "RUSTC");
+    }
+    if let Some(rustc) = &mushroom.rustc {
+        verbose!(&kinoko.ctx, "Mushroom.rustc = {}", rustc);
+        return existing_rustc(program_at(&kinoko.cwd, OsString::from(rustc)), "the mushroom's rustc key");
+    }
+    if let Some(file) = find_toolchain_file(kinoko) {
+        verbose!(&kinoko.ctx, "Toolchain file = {}", file.display());
+        match rustup_which_rustc(kinoko, None) {
+            Ok(rustc) => return existing_rustc(rustc, &file.display().to_string()),
+            Err(reason) => warn!(&kinoko.ctx, "Ignoring {}, {}", file.display(), reason),
+        }
+    }
+    return existing_rustc(OsString::from("rustc"), "the PATH");
+}
+
+/// Paths have to be files, bare names have to be found in the PATH
+fn existing_rustc(rustc: OsString, source: &str) -> Result<OsString, GerminationError> {
+    let path = Path::new(&rustc);
+    let exists = if path.is_absolute() || path.components().count() > 1 {
+        path.is_file()
+    } else {
+        find_in_path(&rustc).is_some()
+    };
+    if !exists {
+        return Err(GerminationError::RustcMissing(rustc.to_string_lossy().to_string(), source.to_string()));
+    }
+    return Ok(rustc);
+}
+
+fn find_in_path(program: &OsStr) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    let mut with_suffix = program.to_os_string();
+    with_suffix.push(env::consts::EXE_SUFFIX);
+    return env::split_paths(&path)
+        .flat_map(|dir| vec![dir.join(program), dir.join(&with_suffix)])
+        .find(|candidate| candidate.is_file());
+}
+
+/// rust-toolchain file of the project, the plain one wins like it does for rustup
+pub fn find_toolchain_file(kinoko: &Kinoko) -> Option<PathBuf> {
+    return TOOLCHAIN_FILES.iter().map(|file| kinoko.cwd.join(file)).find(|path| path.is_file());
+}
+
+/// Paths are relative to `base`, bare names are looked up in the PATH
+fn program_at(base: &Path, program: OsString) -> OsString {
+    let is_path = program.to_string_lossy().contains(|c| c == '/' || c == '\\');
+    return if is_path { base.join(program).into_os_string() } else { program };
+}
+
+/// Asks rustup for the rustc of a toolchain, or of the project's rust-toolchain file when `None`
+fn rustup_which_rustc(kinoko: &Kinoko, toolchain: Option<&String>) -> Result<OsString, String> {
+    let mut cmd = Command::new("rustup");
+    cmd.arg("which").arg("rustc").current_dir(&kinoko.cwd);
+    if let Some(toolchain) = toolchain {
+        cmd.arg("--toolchain").arg(toolchain);
+    }
+    debug!(&kinoko.ctx, "{}", command_line(&cmd));
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(String::from("rustup is not installed")),
+        Err(err) => return Err(format!("rustup failed to run: {}", err)),
+    };
+    if !output.status.success() {
+        // Only rustup's message, without the backtrace it prints with RUST_BACKTRACE set
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or_default().trim();
+        let message = message.strip_prefix("error: ").unwrap_or(message);
+        return Err(if message.is_empty() { String::from("rustup doesn't know of it") } else { message.to_string() });
+    }
+    let rustc = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    return Ok(OsString::from(rustc));
+}
+
+/// `major.minor.patch` of `1.70.0`, `1.70` or the `1.77.0-nightly` of a rustc, pre-releases count as the release
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.split('-').next()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse::<u64>().ok()?;
+    let minor = parts.next()?.parse::<u64>().ok()?;
+    let patch = match parts.next() {
+        Some(patch) => patch.parse::<u64>().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    return Some((major, minor, patch));
+}
+
+/// Fails when `rustc -V` is older than the mushroom's `rust-version`
+pub fn check_rust_version(kinoko: &Kinoko, mushroom: &Mushroom, rustc: &OsStr) -> Result<(), GerminationError> {
+    let required = match &mushroom.rust_version {
+        Some(required) => required,
+        None => return Ok(()),
+    };
+    let required_version = match parse_version(required) {
+        Some(version) => version,
+        None => return Ok(()),
+    };
+    let mut cmd = Command::new(rustc);
+    cmd.arg("-V");
+    debug!(&kinoko.ctx, "{}", command_line(&cmd));
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(err) => return Err(GerminationError::RustcUnavailable(err)),
+    };
+    // rustc 1.75.0 (82e1608df 2023-12-21)
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let found = match stdout.split_whitespace().nth(1) {
+        Some(found) if output.status.success() => found.to_string(),
+        _ => {
+            warn!(&kinoko.ctx, "Couldn't read the version of {}, not checking rust-version {}", rustc.to_string_lossy(), required);
+            return Ok(());
+        },
+    };
+    verbose!(&kinoko.ctx, "rustc version = {}", found);
+    return match parse_version(&found) {
+        Some(found_version) if found_version < required_version => Err(GerminationError::RustcTooOld(required.clone(), found)),
+        Some(_) => Ok(()),
+        None => {
+            warn!(&kinoko.ctx, "Couldn't read the version of {}, not checking rust-version {}", rustc.to_string_lossy(), required);
+            Ok(())
+        },
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_and_short_versions() {
+        assert_eq!(parse_version("1.70.0"), Some((1, 70, 0)));
+        assert_eq!(parse_version("1.70"), Some((1, 70, 0)));
+        assert_eq!(parse_version("1.77.2"), Some((1, 77, 2)));
+    }
+
+    #[test]
+    fn pre_releases_count_as_the_release() {
+        assert_eq!(parse_version("1.77.0-nightly"), Some((1, 77, 0)));
+        assert_eq!(parse_version("1.80.0-beta.3"), Some((1, 80, 0)));
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        assert_eq!(parse_version("1"), None);
+        assert_eq!(parse_version("1.70.0.1"), None);
+        assert_eq!(parse_version("one.two"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn versions_compare_as_tuples() {
+        assert!(parse_version("1.70") < parse_version("1.70.1"));
+        assert!(parse_version("1.9.0") < parse_version("1.10.0"));
+    }
+}
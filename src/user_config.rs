@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+use std::fs;
+
+use kinoko::context::Context;
+use kinoko::utility::{config_dir, warn};
+
+/// Settings shared by all of the user's projects, read from `<config dir>/config`.
+/// Uses the same `key: value` lines as the mushroom
+pub struct UserConfig {
+    pub author: Option<String>,
+}
+impl UserConfig {
+    pub fn path() -> Option<PathBuf> {
+        return config_dir().map(|dir| dir.join("config"));
+    }
+
+    pub fn load(ctx: &Context) -> UserConfig {
+        let mut config = UserConfig { author: None };
+        let path = match UserConfig::path() {
+            Some(path) => path,
+            None => return config,
+        };
+        if !path.is_file() {
+            return config;
+        }
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                warn!(ctx, "Failed to read user config {}: {}", path.display(), err);
+                return config;
+            },
+        };
+        for line in contents.lines() {
+            let (key, val) = match line.split_once(':') {
+                None => continue,
+                Some(key_val) => key_val,
+            };
+            match key.trim() {
+                "author" => config.author = Some(val.trim().to_string()),
+                _ => {},
+            }
+        }
+        return config;
+    }
+}
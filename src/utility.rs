@@ -1,33 +1,46 @@
 use std::path::{Path,PathBuf};
 use std::fs;
 use std::ffi::OsStr;
+use std::env;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use context::Context;
+
+// Every macro takes the `Context` deciding whether and how the message is shown:
+// `info!(ctx, "Germinated {}", head)`
+
+#[macro_export]
+macro_rules! error {
+    ($ctx:expr, $($x:expr),*) => { ($ctx).log($crate::context::LogLevel::Error, format!($($x),*)) }
+}
+pub use error;
+#[macro_export]
+macro_rules! warn {
+    ($ctx:expr, $($x:expr),*) => { ($ctx).log($crate::context::LogLevel::Warn, format!($($x),*)) }
+}
+pub use warn;
 #[macro_export]
 macro_rules! info {
-    ($($x:expr),*) => { println!("[INFO] {}", format!($($x),*)) }
+    ($ctx:expr, $($x:expr),*) => { ($ctx).log($crate::context::LogLevel::Info, format!($($x),*)) }
 }
 pub use info;
 #[macro_export]
-macro_rules! error {
-    ($($x:expr),*) => { eprintln!("[ERROR] {}", format!($($x),*)) }
+macro_rules! verbose {
+    ($ctx:expr, $($x:expr),*) => { ($ctx).log($crate::context::LogLevel::Verbose, format!($($x),*)) }
 }
-pub use error;
-
-static mut CWD : Option<PathBuf> = None;
-pub fn get_cwd() -> PathBuf {
-    let cwd = unsafe { CWD.clone().unwrap() };
-    return cwd;
-}
-pub fn set_cwd(cwd: &PathBuf) {
-    unsafe {
-        CWD = Some(cwd.clone());
-    };
+pub use verbose;
+#[macro_export]
+macro_rules! debug {
+    ($ctx:expr, $($x:expr),*) => { ($ctx).log($crate::context::LogLevel::Debug, format!($($x),*)) }
 }
+pub use debug;
 
-pub fn path_move<P1: AsRef<Path>, P2: AsRef<Path>>(original_path: P1, new_path: P2) -> bool {
+/// Renames a path, the logged paths are shortened relative to the context's folder
+pub fn path_move<P1: AsRef<Path>, P2: AsRef<Path>>(ctx: &Context, original_path: P1, new_path: P2) -> bool {
     let original_path = original_path.as_ref();
     let new_path = new_path.as_ref();
-    let shared_prefix = get_cwd().shared_prefix_with(vec![&original_path, &new_path]);
+    let shared_prefix = ctx.cwd.shared_prefix_with(vec![&original_path, &new_path]);
     let result = fs::rename(&original_path, &new_path);
     let (original_path, new_path) = if let Some(prefix) = shared_prefix {
         let p1 = original_path.strip_prefix(&prefix).unwrap();
@@ -40,11 +53,11 @@ pub fn path_move<P1: AsRef<Path>, P2: AsRef<Path>>(original_path: P1, new_path:
     };
     return match &result {
         Err(err) => {
-            error!("Failed to move {} -> {}: {}", original_path.display(), new_path.display(), err);
+            error!(ctx, "Failed to move {} -> {}: {}", original_path.display(), new_path.display(), err);
             false
         },
         Ok(_) => {
-            info!("{} -> {}", original_path.display(), new_path.display());
+            verbose!(ctx, "{} -> {}", original_path.display(), new_path.display());
             true
         },
     }
@@ -103,3 +116,103 @@ fn path_comps_to_vec(components: std::path::Components) -> Vec<std::path::Compon
     }
     return v;
 }
+
+/// Formats seconds since the unix epoch as a `YYYY-MM-DD HH:MM:SS` UTC date
+pub fn format_unix_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_unix(secs);
+    let rem = secs % 86400;
+    let (hours, minutes, seconds) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+    return format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hours, minutes, seconds);
+}
+
+pub fn current_year() -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    return civil_from_unix(now).0;
+}
+
+/// UTC `(year, month, day)` of a unix timestamp
+fn civil_from_unix(secs: u64) -> (i64, i64, i64) {
+    let days = (secs / 86400) as i64;
+    // Civil from days: http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    return (year, month, day);
+}
+
+/// Folder for the user's kinoko configuration: `$XDG_CONFIG_HOME/kinoko`, `~/.config/kinoko` or `%APPDATA%\kinoko`
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        if !dir.is_empty() {
+            return Some(PathBuf::from(dir).join("kinoko"));
+        }
+    }
+    #[cfg(target_family="windows")]
+    {
+        if let Some(dir) = env::var_os("APPDATA") {
+            return Some(PathBuf::from(dir).join("kinoko"));
+        }
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    return Some(PathBuf::from(home).join(".config").join("kinoko"));
+}
+
+/// Quotes an argument for a POSIX shell, plain words are left as they are
+pub fn shell_quote<S: AsRef<OsStr>>(arg: S) -> String {
+    let arg = arg.as_ref().to_string_lossy();
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    return format!("'{}'", arg.replace('\'', "'\\''"));
+}
+
+/// Command line of a command as it could be pasted into a shell, the environment variables it sets come first
+pub fn command_line(cmd: &Command) -> String {
+    let mut line = String::new();
+    for (name, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            line.push_str(&format!("{}={} ", name.to_string_lossy(), shell_quote(value)));
+        }
+    }
+    line.push_str(&shell_quote(cmd.get_program()));
+    for arg in cmd.get_args() {
+        line.push(' ');
+        line.push_str(&shell_quote(arg));
+    }
+    return line;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_words_stay_unquoted() {
+        assert_eq!(shell_quote("rustc"), "rustc");
+        assert_eq!(shell_quote("-Copt-level=3"), "-Copt-level=3");
+        assert_eq!(shell_quote("/tmp/a_b/main.rs"), "/tmp/a_b/main.rs");
+    }
+
+    #[test]
+    fn everything_else_is_single_quoted() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("feature=\"fancy\""), "'feature=\"fancy\"'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn command_line_puts_the_environment_first() {
+        let mut cmd = Command::new("rustc");
+        cmd.env("KINOKO_PKG_DESCRIPTION", "A tool").arg("-o").arg("build/my app");
+        assert_eq!(command_line(&cmd), "KINOKO_PKG_DESCRIPTION='A tool' rustc -o 'build/my app'");
+    }
+}